@@ -0,0 +1,251 @@
+use std::borrow::Cow;
+use std::path::{Component, Path, PathBuf};
+use std::{fs, io};
+
+use anyhow::Context as _;
+use mdbook_preprocessor::book::SectionNumber;
+use mdbook_preprocessor::config::Config;
+use mdbook_preprocessor::errors::Error;
+
+use crate::code::gutter_css;
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::{HeadingRender, NumberingConfig, NumberingStyle};
+
+static HIGHLIGHT_JS_LINE_NUMBERS_JS: &str = include_str!("highlightjs/line-numbers-min.js");
+
+static HIGHLIGHT_JS_LINE_NUMBERS_CSS: &str = include_str!("highlightjs/line-numbers-min.css");
+
+static SECTION_NUMBERS_CSS: &str = include_str!("heading/numbering-min.css");
+
+static SECTION_NUMBERS_ATTRIBUTE_CSS: &str = include_str!("heading/attribute-min.css");
+
+static SECTION_NUMBERS_PRINT_HIDE_CSS: &str = include_str!("heading/hide-min.css");
+
+/// Default directory, relative to the book root, to write assets to.
+const DEFAULT_ASSETS_DIR: &str = "mdbook-numbering";
+
+/// File name of the CSS asset written in `files` mode.
+const CSS_FILE_NAME: &str = "numbering.css";
+
+/// File name of the JavaScript asset written in `files` mode.
+const JS_FILE_NAME: &str = "numbering.js";
+
+/// CSS and JavaScript used by the rendered book.
+#[derive(Debug, Clone)]
+pub(crate) struct Assets {
+    heading_css: Cow<'static, str>,
+    hide_css: Cow<'static, str>,
+    code_css: Cow<'static, str>,
+    code_js: Cow<'static, str>,
+    extra_css: String,
+    extra_js: String,
+}
+
+impl Assets {
+    /// Create the assets for `config`, using the embedded files.
+    pub(crate) fn new(config: &NumberingConfig) -> Self {
+        Self {
+            heading_css: Cow::Borrowed(match config.heading.render {
+                HeadingRender::Span => SECTION_NUMBERS_CSS,
+                HeadingRender::AttributeOnly => SECTION_NUMBERS_ATTRIBUTE_CSS,
+            }),
+            hide_css: Cow::Borrowed(SECTION_NUMBERS_PRINT_HIDE_CSS),
+            code_css: Cow::Owned(format!(
+                "{HIGHLIGHT_JS_LINE_NUMBERS_CSS}{}",
+                gutter_css(&config.code)
+            )),
+            code_js: Cow::Borrowed(HIGHLIGHT_JS_LINE_NUMBERS_JS),
+            extra_css: String::new(),
+            extra_js: String::new(),
+        }
+    }
+
+    /// Create the assets for `config`, reading the replacement and additional files
+    /// configured in [`AssetsConfig`](crate::AssetsConfig) relative to `root`.
+    pub(crate) fn load(config: &NumberingConfig, root: &Path) -> Result<Self, Error> {
+        let read = |path: &Path| {
+            let path = root.join(path);
+            fs::read_to_string(&path)
+                .with_context(|| format!("Failed to read asset file `{}`", path.display()))
+        };
+        let replace = |asset: &mut Cow<'static, str>, path: &Option<PathBuf>| {
+            if let Some(path) = path {
+                *asset = Cow::Owned(read(path)?);
+            }
+            Ok::<_, Error>(())
+        };
+
+        let config_assets = &config.assets;
+        let mut assets = Self::new(config);
+        replace(&mut assets.heading_css, &config_assets.heading_css)?;
+        replace(&mut assets.hide_css, &config_assets.hide_css)?;
+        if let Some(path) = &config_assets.code_css {
+            assets.code_css = Cow::Owned(format!("{}\n{}", read(path)?, gutter_css(&config.code)));
+        }
+        replace(&mut assets.code_js, &config_assets.code_js)?;
+        for path in &config_assets.extra_css {
+            append(&mut assets.extra_css, &read(path)?, "\n");
+        }
+        for path in &config_assets.extra_js {
+            append(&mut assets.extra_js, &read(path)?, ";\n");
+        }
+        Ok(assets)
+    }
+
+    /// The `<style>` element for heading numbers.
+    pub(crate) fn heading_style(&self) -> String {
+        format!("<style>{}</style>\n", self.heading_css)
+    }
+
+    /// The `<style>` element that hides headings added by mdBook when printing.
+    pub(crate) fn hide_style(&self) -> String {
+        format!("<style>{}</style>\n", self.hide_css)
+    }
+
+    /// The `<style>` element for code block line numbers.
+    pub(crate) fn code_style(&self) -> String {
+        format!("<style>\n{}\n</style>\n", self.code_css)
+    }
+
+    /// The `<script>` element for code block line numbers.
+    pub(crate) fn code_script(&self) -> String {
+        format!(
+            "<script defer>\n{}</script>\n",
+            Self::on_content_loaded(&self.code_js),
+        )
+    }
+
+    /// The `<style>` element for additional CSS, if any.
+    pub(crate) fn extra_style(&self) -> Option<String> {
+        (!self.extra_css.is_empty()).then(|| format!("<style>\n{}</style>\n", self.extra_css))
+    }
+
+    /// The `<script>` element for additional JavaScript, if any.
+    pub(crate) fn extra_script(&self) -> Option<String> {
+        (!self.extra_js.is_empty()).then(|| format!("<script>\n{}</script>\n", self.extra_js))
+    }
+
+    /// The elements appended to a chapter numbered `number` in `inline` mode.
+    pub(crate) fn inline(
+        &self,
+        config: &NumberingConfig,
+        number: Option<&SectionNumber>,
+    ) -> Vec<String> {
+        let mut elements = Vec::new();
+        if let Some(number) = number.filter(|_| config.heading.enable) {
+            elements.push(self.heading_style());
+            if config.heading.numbering_style == NumberingStyle::Consecutive && number.len() > 1 {
+                elements.push(self.hide_style());
+            }
+        }
+        if config.code.enable {
+            elements.push(self.code_script());
+            elements.push(self.code_style());
+        }
+        elements.extend(self.extra_style());
+        elements.extend(self.extra_script());
+        elements
+    }
+
+    fn on_content_loaded(js: &str) -> String {
+        format!("window.addEventListener('DOMContentLoaded', function() {{ {js} }});\n")
+    }
+
+    /// Content of the CSS file written in `files` mode.
+    ///
+    /// As the file is shared by all chapters, the CSS that hides headings
+    /// added by mdBook is included whenever `numbering-style` is `consecutive`.
+    pub(crate) fn css_file(&self, config: &NumberingConfig) -> String {
+        let mut css = String::new();
+        if config.heading.enable {
+            css.push_str(&self.heading_css);
+            if config.heading.numbering_style == NumberingStyle::Consecutive {
+                css.push_str(&self.hide_css);
+            }
+        }
+        if config.code.enable {
+            css.push_str(&self.code_css);
+            css.push('\n');
+        }
+        css.push_str(&self.extra_css);
+        css
+    }
+
+    /// Content of the JavaScript file written in `files` mode.
+    pub(crate) fn js_file(&self, config: &NumberingConfig) -> String {
+        let mut js = String::new();
+        if config.code.enable {
+            js.push_str(&Self::on_content_loaded(&self.code_js));
+        }
+        js.push_str(&self.extra_js);
+        js
+    }
+
+    /// Write the asset files to the assets directory under `root`,
+    /// and return the paths of the files relative to `root`.
+    ///
+    /// Files are only written if their content changes,
+    /// so that `mdbook serve` does not rebuild the book again and again.
+    pub(crate) fn write(&self, config: &NumberingConfig, root: &Path) -> io::Result<[PathBuf; 2]> {
+        let dir = config
+            .assets
+            .dir
+            .as_deref()
+            .unwrap_or(Path::new(DEFAULT_ASSETS_DIR));
+        fs::create_dir_all(root.join(dir))?;
+
+        let css = dir.join(CSS_FILE_NAME);
+        let js = dir.join(JS_FILE_NAME);
+        for (path, content) in [(&css, self.css_file(config)), (&js, self.js_file(config))] {
+            let path = root.join(path);
+            if fs::read_to_string(&path).ok().as_deref() != Some(content.as_str()) {
+                fs::write(&path, content)?;
+            }
+        }
+        Ok([css, js])
+    }
+}
+
+/// Append `content` to `buf`, after `separator` if `buf` is not empty,
+/// so that a file without a trailing newline or semicolon does not run into the next one.
+fn append(buf: &mut String, content: &str, separator: &str) {
+    if !buf.is_empty() {
+        buf.push_str(separator);
+    }
+    buf.push_str(content);
+}
+
+/// Check that the asset files are referenced in `output.html`,
+/// and report each missing reference.
+pub(crate) fn check_references(
+    config: &Config,
+    [css, js]: &[PathBuf; 2],
+    mut cb: impl FnMut(Error),
+) {
+    // `./` components are ignored, as mdBook resolves both paths against the book root.
+    fn normalize(path: &Path) -> PathBuf {
+        (path.components())
+            .filter(|component| *component != Component::CurDir)
+            .collect()
+    }
+
+    for (key, path) in [("additional-css", css), ("additional-js", js)] {
+        let list: Vec<PathBuf> = config
+            .get(&format!("output.html.{key}"))
+            .ok()
+            .flatten()
+            .unwrap_or_default();
+        if !list
+            .iter()
+            .any(|listed| normalize(listed) == normalize(path))
+        {
+            let message = format!(
+                "`{path}` is not listed in `output.html.{key}`, \
+                so it will not be loaded by the rendered book. \
+                Consider adding `{key} = [\"{path}\"]` to `[output.html]`.",
+                path = path.display(),
+            );
+            cb(Diagnostic::new(DiagnosticKind::AssetNotReferenced, message).into());
+        }
+    }
+}
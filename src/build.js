@@ -16,6 +16,10 @@ const results = await Promise.all([
     entrypoints: ["src/heading/hide.css"],
     minify: true,
   }),
+  Bun.build({
+    entrypoints: ["src/heading/attribute.css"],
+    minify: true,
+  }),
 ]);
 
 const outfiles = [
@@ -23,6 +27,7 @@ const outfiles = [
   "src/highlightjs/line-numbers-min.css",
   "src/heading/numbering-min.css",
   "src/heading/hide-min.css",
+  "src/heading/attribute-min.css",
 ];
 
 await Promise.all(
@@ -0,0 +1,358 @@
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::fmt::Write as _;
+use std::ops::Range;
+
+use pulldown_cmark::{CodeBlockKind, CowStr, Event, Tag, TagEnd};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::{CodeConfig, GutterStyle};
+
+/// Info string flag that makes a code block continue the line numbering of
+/// the previous code block, or of the code block named after `=`.
+const LN_CONTINUE: &str = "ln-continue";
+/// Info string flag that names a code block, so that later code blocks can
+/// continue its line numbering with `ln-continue=<id>`.
+const LN_ID: &str = "ln-id";
+/// Info string token added to code blocks that continue the line numbering,
+/// followed by `-<n>` where `<n>` is the first line number.
+const LN_START_FROM: &str = "ln-start-from";
+
+/// Default color, border and padding of the line number gutter.
+const DEFAULT_GUTTER: [&str; 3] = ["#ccc", "1px solid #ccc", "0 5px 0 0"];
+
+/// Default gutter colors and borders for dark mdBook themes,
+/// matching the dimmed text colors of those themes.
+const DEFAULT_THEME_GUTTERS: [(&str, [&str; 2]); 3] = [
+    ("ayu", ["#5c6773", "1px solid #5c6773"]),
+    ("coal", ["#505254", "1px solid #505254"]),
+    ("navy", ["#505274", "1px solid #505274"]),
+];
+
+/// Generate the CSS for the line number gutter from `config`.
+///
+/// Built-in theme defaults only apply to fields that are not set in
+/// [`CodeConfig::gutter`], while fields set in [`CodeConfig::theme`]
+/// always take precedence.
+pub(crate) fn gutter_css(config: &CodeConfig) -> String {
+    fn fields(style: &GutterStyle) -> [Option<&str>; 3] {
+        [&style.color, &style.border, &style.padding].map(Option::as_deref)
+    }
+
+    fn push_rule(css: &mut String, selector: &str, style: [Option<&str>; 3]) {
+        let properties = ["color", "border-right", "padding"];
+        let mut declarations = properties
+            .into_iter()
+            .zip(style)
+            .filter_map(|(property, value)| Some((property, value?)));
+        let Some((property, value)) = declarations.next() else {
+            return;
+        };
+        let _ = write!(css, "{selector}{{{property}:{value}");
+        for (property, value) in declarations {
+            let _ = write!(css, ";{property}:{value}");
+        }
+        css.push('}');
+    }
+
+    let gutter = fields(&config.gutter);
+
+    let mut css = String::new();
+    push_rule(
+        &mut css,
+        ".hljs-ln-n",
+        [0, 1, 2].map(|i| gutter[i].or(Some(DEFAULT_GUTTER[i]))),
+    );
+
+    let mut themes: BTreeMap<&str, [Option<&str>; 3]> = DEFAULT_THEME_GUTTERS
+        .iter()
+        .map(|(theme, [color, border])| {
+            let style = [Some(*color), Some(*border), None];
+            (
+                *theme,
+                [0, 1, 2].map(|i| style[i].filter(|_| gutter[i].is_none())),
+            )
+        })
+        .collect();
+    for (theme, style) in &config.theme {
+        let entry = themes.entry(theme).or_default();
+        for (field, value) in entry.iter_mut().zip(fields(style)) {
+            *field = value.or(*field);
+        }
+    }
+    for (theme, style) in themes {
+        push_rule(&mut css, &format!(".{theme} .hljs-ln-n"), style);
+    }
+
+    css
+}
+
+/// Line numbering flags found in the info string of a fenced code block.
+#[derive(Debug, Default)]
+struct LineFlags<'a> {
+    /// `Some(None)` for `ln-continue`, `Some(Some(id))` for `ln-continue=<id>`.
+    continue_from: Option<Option<&'a str>>,
+    /// The value of `ln-id=<id>`.
+    id: Option<&'a str>,
+}
+
+impl<'a> LineFlags<'a> {
+    fn parse(info: &'a str) -> Self {
+        let mut flags = Self::default();
+        for token in info_tokens(info) {
+            match token.split_once('=') {
+                None if token == LN_CONTINUE => flags.continue_from = Some(None),
+                Some((LN_CONTINUE, id)) => flags.continue_from = Some(Some(id)),
+                Some((LN_ID, id)) => flags.id = Some(id),
+                _ => {}
+            }
+        }
+        flags
+    }
+}
+
+/// Split an info string the same way mdBook does when rendering code blocks.
+fn info_tokens(info: &str) -> impl Iterator<Item = &str> {
+    info.split([' ', '\t', ','])
+        .filter(|token| !token.is_empty())
+}
+
+/// Add the `hljs` class to `<code>` elements directly inside `<pre>` elements in `html`.
+///
+/// Returns `None` if nothing needs to be changed.
+fn mark_html_code(html: &str) -> Option<String> {
+    fn starts_with_tag(html: &str, name: &str) -> bool {
+        html.strip_prefix('<')
+            .and_then(|html| html.strip_prefix(name))
+            .is_some_and(|html| html.starts_with(|c: char| c == '>' || c.is_whitespace()))
+    }
+
+    let mut buf = String::new();
+    let mut rest = html;
+    while let Some(pre) = rest.find("<pre") {
+        let Some(pre_end) = rest[pre..].find('>').map(|i| pre + i + 1) else {
+            break;
+        };
+        let after_pre = &rest[pre_end..];
+        let code = pre_end + (after_pre.len() - after_pre.trim_start().len());
+        if !starts_with_tag(&rest[pre..], "pre") || !starts_with_tag(&rest[code..], "code") {
+            buf.push_str(&rest[..pre_end]);
+            rest = &rest[pre_end..];
+            continue;
+        }
+        let Some(code_end) = rest[code..].find('>').map(|i| code + i) else {
+            break;
+        };
+        let tag = &rest[code..code_end];
+        match tag.find("class=\"") {
+            Some(class) => {
+                let class = code + class + "class=\"".len();
+                let classes = rest[class..code_end].split('"').next().unwrap_or_default();
+                if classes.split_whitespace().any(|class| class == "hljs") {
+                    buf.push_str(&rest[..code_end]);
+                } else {
+                    buf.push_str(&rest[..class]);
+                    buf.push_str("hljs ");
+                    buf.push_str(&rest[class..code_end]);
+                }
+            }
+            None => {
+                buf.push_str(&rest[..code + "<code".len()]);
+                buf.push_str(" class=\"hljs\"");
+                buf.push_str(&rest[code + "<code".len()..code_end]);
+            }
+        }
+        rest = &rest[code_end..];
+    }
+    if buf.is_empty() {
+        return None;
+    }
+    buf.push_str(rest);
+    (buf != html).then_some(buf)
+}
+
+pub(crate) fn escape_html(text: &str, buf: &mut String) {
+    for c in text.chars() {
+        match c {
+            '&' => buf.push_str("&amp;"),
+            '<' => buf.push_str("&lt;"),
+            '>' => buf.push_str("&gt;"),
+            '"' => buf.push_str("&quot;"),
+            _ => buf.push(c),
+        }
+    }
+}
+
+/// Iterator adapter that resolves line numbering flags of code blocks.
+///
+/// Code blocks that continue the numbering of an earlier code block get an
+/// `ln-start-from-<n>` token after the language in their info string.
+/// mdBook turns it into a class of the `<code>` element,
+/// which is picked up by `highlightjs-line-numbers.js`.
+///
+/// If [`CodeConfig::all_blocks`] is set, indented code blocks and
+/// `<pre><code>` HTML blocks are normalized as well.
+///
+/// Events are paired with their source ranges, as yielded by
+/// [`Parser::into_offset_iter`](pulldown_cmark::Parser::into_offset_iter).
+/// Replaced code blocks keep the range of the original block.
+pub(crate) struct CodeBlocks<'a, I> {
+    events: I,
+    config: &'a CodeConfig,
+    /// Name of the chapter, used in warnings.
+    name: &'a str,
+    /// Events that are already processed but not yet yielded.
+    pending: VecDeque<(Event<'a>, Range<usize>)>,
+    /// The line number the next code block would start from if it continues
+    /// the previous one.
+    next_line: u32,
+    /// The line number following each named code block.
+    named: HashMap<String, u32>,
+    /// Warnings found while processing code blocks,
+    /// with the source offset of the code block.
+    pub(crate) warnings: Vec<(usize, Diagnostic)>,
+    /// Code blocks replaced with HTML, with the source range of the original block.
+    ///
+    /// The HTML is as it would be in a block without container prefixes like `> `.
+    pub(crate) replaced: Vec<(Range<usize>, String)>,
+    /// `ln-start-from-<n>` tokens added to info strings,
+    /// with the source offset of the code block.
+    pub(crate) continued: Vec<(usize, String)>,
+}
+
+impl<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> CodeBlocks<'a, I> {
+    pub(crate) fn new(events: I, config: &'a CodeConfig, name: &'a str) -> Self {
+        Self {
+            events,
+            config,
+            name,
+            pending: VecDeque::new(),
+            next_line: 1,
+            named: HashMap::new(),
+            warnings: Vec::new(),
+            replaced: Vec::new(),
+            continued: Vec::new(),
+        }
+    }
+
+    /// Consume a code block up to and including its end tag,
+    /// returning its events and its content.
+    fn collect_block(&mut self) -> (Vec<(Event<'a>, Range<usize>)>, String) {
+        let mut events = Vec::new();
+        let mut content = String::new();
+        for (event, range) in self.events.by_ref() {
+            match &event {
+                Event::Text(text) => content.push_str(text),
+                Event::End(TagEnd::CodeBlock) => {
+                    events.push((event, range));
+                    break;
+                }
+                _ => {}
+            }
+            events.push((event, range));
+        }
+        (events, content)
+    }
+
+    fn process(&mut self, start: Event<'a>, range: Range<usize>, info: CowStr<'a>) {
+        let flags = LineFlags::parse(&info);
+
+        let start_from = match flags.continue_from {
+            None => 1,
+            Some(None) => self.next_line,
+            Some(Some(id)) => match self.named.get(id) {
+                Some(&line) => line,
+                None => {
+                    let warning = Diagnostic::new(
+                        DiagnosticKind::UnknownCodeBlockId,
+                        format_args!(
+                            "\
+                            Code block `{id}` referenced by `{LN_CONTINUE}={id}` \
+                            is not found in chapter \"{}\". \
+                            Make sure a previous code block is marked with `{LN_ID}={id}`.",
+                            self.name,
+                        ),
+                    );
+                    self.warnings.push((range.start, warning));
+                    1
+                }
+            },
+        };
+
+        let (events, content) = self.collect_block();
+        let lines = content.lines().count() as u32;
+
+        self.next_line = start_from + lines;
+        if let Some(id) = flags.id {
+            self.named.insert(id.to_owned(), self.next_line);
+        }
+
+        if flags.continue_from.is_some() {
+            let token = format!("{LN_START_FROM}-{start_from}");
+            let language_end = info.find([' ', '\t', ',']).unwrap_or(info.len());
+            let info = format!("{},{token}{}", &info[..language_end], &info[language_end..]);
+            self.continued.push((range.start, token));
+            let start = Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info.into())));
+            self.pending.push_back((start, range));
+        } else {
+            self.pending.push_back((start, range));
+        }
+        self.pending.extend(events);
+    }
+}
+
+impl<'a, I: Iterator<Item = (Event<'a>, Range<usize>)>> Iterator for CodeBlocks<'a, I> {
+    type Item = (Event<'a>, Range<usize>);
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(item) = self.pending.pop_front() {
+            return Some(item);
+        }
+        let (event, range) = self.events.next()?;
+        if !self.config.enable {
+            return Some((event, range));
+        }
+        match event {
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(ref info))) => {
+                let info = info.clone();
+                self.process(event, range, info);
+                self.pending.pop_front()
+            }
+            Event::Start(Tag::CodeBlock(CodeBlockKind::Indented)) if self.config.all_blocks => {
+                let info = CowStr::Borrowed("");
+                let event = Event::Start(Tag::CodeBlock(CodeBlockKind::Fenced(info.clone())));
+                self.process(event, range, info);
+                self.pending.pop_front()
+            }
+            Event::Start(Tag::HtmlBlock) if self.config.all_blocks => {
+                let mut events = Vec::new();
+                let mut html = String::new();
+                for (event, range) in self.events.by_ref() {
+                    match &event {
+                        Event::Html(text) | Event::Text(text) => html.push_str(text),
+                        Event::End(TagEnd::HtmlBlock) => {
+                            events.push((event, range));
+                            break;
+                        }
+                        _ => {}
+                    }
+                    events.push((event, range));
+                }
+                match mark_html_code(&html) {
+                    Some(html) => {
+                        self.replaced.push((range.clone(), html.clone()));
+                        self.pending.extend(
+                            [
+                                Event::Html(CowStr::from(html)),
+                                Event::End(TagEnd::HtmlBlock),
+                            ]
+                            .map(|event| (event, range.clone())),
+                        );
+                    }
+                    None => self.pending.extend(events),
+                }
+                Some((event, range))
+            }
+            _ => Some((event, range)),
+        }
+    }
+}
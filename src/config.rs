@@ -1,3 +1,8 @@
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+
+use anyhow::{Context as _, anyhow};
+use mdbook_preprocessor::errors::Error;
 use serde::de::IgnoredAny;
 use serde::{Deserialize, Serialize};
 
@@ -49,6 +54,44 @@ impl Default for NumberingStyle {
     }
 }
 
+/// How heading numbers are rendered by the `mdbook-numbering` preprocessor.
+///
+/// Should be placed under the `render` field
+/// in the `[preprocessor.numbering.heading]` section in `book.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub enum HeadingRender {
+    /// Insert the number as a `<span class="heading numbering">` element
+    /// at the beginning of the heading,
+    /// and also store it in the `data-numbering` attribute of the heading.
+    ///
+    /// This is the default behavior of `mdbook-numbering`.
+    Span,
+    /// Only store the number in the `data-numbering` attribute of the heading,
+    /// and render it with a CSS `::before` pseudo-element.
+    ///
+    /// As the number is not part of the heading text,
+    /// it is neither copied along with the heading text
+    /// nor included in the search index of mdBook.
+    AttributeOnly,
+    // Future rendering methods can be added here.
+}
+
+impl HeadingRender {
+    /// Create a new `HeadingRender` with default value.
+    pub const fn new() -> Self {
+        Self::Span
+    }
+}
+
+impl Default for HeadingRender {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 fn bool_true() -> bool {
     true
 }
@@ -65,9 +108,12 @@ pub struct HeadingConfig {
     /// Whether to enable heading numbering.
     #[serde(default = "bool_true")]
     pub enable: bool,
-    /// Whether to treat warnings as errors.
+    /// The numbering style of headings.
     #[serde(default)]
     pub numbering_style: NumberingStyle,
+    /// How heading numbers are rendered.
+    #[serde(default)]
+    pub render: HeadingRender,
     // Future configuration options can be added here.
 }
 
@@ -77,8 +123,27 @@ impl HeadingConfig {
         Self {
             enable: true,
             numbering_style: NumberingStyle::new(),
+            render: HeadingRender::new(),
         }
     }
+
+    /// Set [`enable`](Self::enable).
+    pub fn enable(mut self, enable: bool) -> Self {
+        self.enable = enable;
+        self
+    }
+
+    /// Set [`numbering_style`](Self::numbering_style).
+    pub fn numbering_style(mut self, numbering_style: NumberingStyle) -> Self {
+        self.numbering_style = numbering_style;
+        self
+    }
+
+    /// Set [`render`](Self::render).
+    pub fn render(mut self, render: HeadingRender) -> Self {
+        self.render = render;
+        self
+    }
 }
 
 impl Default for HeadingConfig {
@@ -87,11 +152,61 @@ impl Default for HeadingConfig {
     }
 }
 
+/// Style of the line number gutter of code blocks.
+///
+/// Each field is a CSS value. Fields that are not set fall back to
+/// the built-in defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct GutterStyle {
+    /// Color of line numbers, e.g. `"#ccc"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub color: Option<String>,
+    /// Border between line numbers and code, e.g. `"1px solid #ccc"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub border: Option<String>,
+    /// Padding around line numbers, e.g. `"0 5px 0 0"`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub padding: Option<String>,
+    // Future configuration options can be added here.
+}
+
+impl GutterStyle {
+    /// Create a new `GutterStyle` with all fields unset.
+    pub const fn new() -> Self {
+        Self {
+            color: None,
+            border: None,
+            padding: None,
+        }
+    }
+
+    /// Set [`color`](Self::color).
+    pub fn color(mut self, color: impl Into<String>) -> Self {
+        self.color = Some(color.into());
+        self
+    }
+
+    /// Set [`border`](Self::border).
+    pub fn border(mut self, border: impl Into<String>) -> Self {
+        self.border = Some(border.into());
+        self
+    }
+
+    /// Set [`padding`](Self::padding).
+    pub fn padding(mut self, padding: impl Into<String>) -> Self {
+        self.padding = Some(padding.into());
+        self
+    }
+}
+
 /// Configuration for code block line numbering.
 ///
 /// Should be placed under the `code` field
 /// in the `[preprocessor.numbering]` section in `book.toml`.
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 #[non_exhaustive]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
@@ -99,13 +214,61 @@ pub struct CodeConfig {
     /// Whether to enable code numbering.
     #[serde(default = "bool_true")]
     pub enable: bool,
+    /// Whether to also number indented code blocks and raw `<pre><code>` HTML blocks.
+    ///
+    /// Indented code blocks are rewritten as fenced code blocks,
+    /// so that they can take part in `ln-continue`,
+    /// and `<code>` elements directly inside `<pre>` elements in HTML blocks
+    /// are given the `hljs` class, which is what the line numbering script looks for.
+    #[serde(default)]
+    pub all_blocks: bool,
+    /// Style of the line number gutter.
+    #[serde(default)]
+    pub gutter: GutterStyle,
+    /// Overrides of the gutter style for mdBook themes,
+    /// keyed by theme class (e.g. `light`, `rust`, `coal`, `navy` and `ayu`).
+    ///
+    /// Built-in overrides exist for the dark themes `coal`, `navy` and `ayu`,
+    /// and fields set here take precedence over them.
+    #[serde(default)]
+    pub theme: BTreeMap<String, GutterStyle>,
     // Future configuration options can be added here.
 }
 
 impl CodeConfig {
     /// Create a new `CodeConfig` with default values.
     pub const fn new() -> Self {
-        Self { enable: true }
+        Self {
+            enable: true,
+            all_blocks: false,
+            gutter: GutterStyle::new(),
+            theme: BTreeMap::new(),
+        }
+    }
+
+    /// Set [`enable`](Self::enable).
+    pub fn enable(mut self, enable: bool) -> Self {
+        self.enable = enable;
+        self
+    }
+
+    /// Set [`all_blocks`](Self::all_blocks).
+    pub fn all_blocks(mut self, all_blocks: bool) -> Self {
+        self.all_blocks = all_blocks;
+        self
+    }
+
+    /// Set [`gutter`](Self::gutter).
+    pub fn gutter(mut self, gutter: GutterStyle) -> Self {
+        self.gutter = gutter;
+        self
+    }
+
+    /// Set the gutter style for the mdBook theme with class `name`
+    /// in [`theme`](Self::theme).
+    pub fn theme(mut self, name: impl Into<String>, style: GutterStyle) -> Self {
+        self.theme.insert(name.into(), style);
+        self
     }
 }
 
@@ -115,35 +278,428 @@ impl Default for CodeConfig {
     }
 }
 
+/// How the CSS and JavaScript used by `mdbook-numbering` are delivered to the rendered book.
+///
+/// Should be placed under the `mode` field
+/// in the `[preprocessor.numbering.assets]` section in `book.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub enum AssetsMode {
+    /// Inject `<style>` and `<script>` elements into every chapter.
+    ///
+    /// This works out of the box, but pages that combine all chapters,
+    /// such as `print.html`, contain one copy of the assets per chapter.
+    Inline,
+    /// Write the assets once to `numbering.css` and `numbering.js`
+    /// in [`AssetsConfig::dir`], and leave chapters with only markup.
+    ///
+    /// The files should be listed in `additional-css` and `additional-js`
+    /// in the `[output.html]` section in `book.toml`, for example:
+    ///
+    /// ```toml
+    /// [output.html]
+    /// additional-css = ["mdbook-numbering/numbering.css"]
+    /// additional-js  = ["mdbook-numbering/numbering.js"]
+    /// ```
+    Files,
+}
+
+impl AssetsMode {
+    /// Create a new `AssetsMode` with default value.
+    pub const fn new() -> Self {
+        Self::Inline
+    }
+}
+
+impl Default for AssetsMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configuration for the CSS and JavaScript assets.
+///
+/// Should be placed under the `assets` field
+/// in the `[preprocessor.numbering]` section in `book.toml`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct AssetsConfig {
+    /// How the assets are delivered to the rendered book.
+    #[serde(default)]
+    pub mode: AssetsMode,
+    /// Directory to write the asset files to in [`AssetsMode::Files`] mode,
+    /// relative to the book root. Defaults to `mdbook-numbering`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub dir: Option<PathBuf>,
+    /// CSS file that replaces the embedded CSS for heading numbers
+    /// (`numbering-min.css`), relative to the book root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub heading_css: Option<PathBuf>,
+    /// CSS file that replaces the embedded CSS hiding headings added by mdBook
+    /// when printing (`hide-min.css`), relative to the book root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hide_css: Option<PathBuf>,
+    /// CSS file that replaces the embedded CSS for code block line numbers
+    /// (`line-numbers-min.css`), relative to the book root.
+    ///
+    /// The CSS generated from [`CodeConfig::gutter`] and [`CodeConfig::theme`]
+    /// is still appended to it.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_css: Option<PathBuf>,
+    /// JavaScript file that replaces the embedded script for code block line numbers
+    /// (`line-numbers-min.js`), relative to the book root.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub code_js: Option<PathBuf>,
+    /// Additional CSS files appended after the built-in CSS, relative to the book root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_css: Vec<PathBuf>,
+    /// Additional JavaScript files appended after the built-in scripts,
+    /// relative to the book root.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extra_js: Vec<PathBuf>,
+    // Future configuration options can be added here.
+}
+
+impl AssetsConfig {
+    /// Create a new `AssetsConfig` with default values.
+    pub const fn new() -> Self {
+        Self {
+            mode: AssetsMode::new(),
+            dir: None,
+            heading_css: None,
+            hide_css: None,
+            code_css: None,
+            code_js: None,
+            extra_css: Vec::new(),
+            extra_js: Vec::new(),
+        }
+    }
+
+    /// Set [`mode`](Self::mode).
+    pub fn mode(mut self, mode: AssetsMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Set [`dir`](Self::dir).
+    pub fn dir(mut self, dir: impl Into<PathBuf>) -> Self {
+        self.dir = Some(dir.into());
+        self
+    }
+
+    /// Set [`heading_css`](Self::heading_css).
+    pub fn heading_css(mut self, heading_css: impl Into<PathBuf>) -> Self {
+        self.heading_css = Some(heading_css.into());
+        self
+    }
+
+    /// Set [`hide_css`](Self::hide_css).
+    pub fn hide_css(mut self, hide_css: impl Into<PathBuf>) -> Self {
+        self.hide_css = Some(hide_css.into());
+        self
+    }
+
+    /// Set [`code_css`](Self::code_css).
+    pub fn code_css(mut self, code_css: impl Into<PathBuf>) -> Self {
+        self.code_css = Some(code_css.into());
+        self
+    }
+
+    /// Set [`code_js`](Self::code_js).
+    pub fn code_js(mut self, code_js: impl Into<PathBuf>) -> Self {
+        self.code_js = Some(code_js.into());
+        self
+    }
+
+    /// Set [`extra_css`](Self::extra_css).
+    pub fn extra_css(mut self, extra_css: Vec<PathBuf>) -> Self {
+        self.extra_css = extra_css;
+        self
+    }
+
+    /// Set [`extra_js`](Self::extra_js).
+    pub fn extra_js(mut self, extra_js: Vec<PathBuf>) -> Self {
+        self.extra_js = extra_js;
+        self
+    }
+}
+
+impl Default for AssetsConfig {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Configuration for machine-readable diagnostics.
+///
+/// Should be placed under the `diagnostics` field
+/// in the `[preprocessor.numbering]` section in `book.toml`.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub struct DiagnosticsConfig {
+    /// File to write diagnostics to as JSON lines, relative to the book root,
+    /// or `-` for stderr.
+    ///
+    /// They are written in addition to the human-readable messages,
+    /// one JSON object per line with the fields `severity`, `code`, `name`,
+    /// `message`, `file`, `line`, `column` and `chapter`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub json: Option<PathBuf>,
+    // Future configuration options can be added here.
+}
+
+impl DiagnosticsConfig {
+    /// Create a new `DiagnosticsConfig` with default values.
+    pub const fn new() -> Self {
+        Self { json: None }
+    }
+
+    /// Set [`json`](Self::json).
+    pub fn json(mut self, json: impl Into<PathBuf>) -> Self {
+        self.json = Some(json.into());
+        self
+    }
+}
+
+/// A Markdown extension of `pulldown-cmark` that can be enabled when parsing chapters.
+///
+/// Should be placed in the `markdown-extensions` list
+/// in the `[preprocessor.numbering]` section in `book.toml`.
+///
+/// Each variant corresponds to a flag in [`pulldown_cmark::Options`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub enum MarkdownExtension {
+    /// `ENABLE_TABLES`.
+    Tables,
+    /// `ENABLE_FOOTNOTES`.
+    Footnotes,
+    /// `ENABLE_STRIKETHROUGH`.
+    Strikethrough,
+    /// `ENABLE_TASKLISTS`.
+    Tasklists,
+    /// `ENABLE_SMART_PUNCTUATION`.
+    SmartPunctuation,
+    /// `ENABLE_HEADING_ATTRIBUTES`.
+    HeadingAttributes,
+    /// `ENABLE_YAML_STYLE_METADATA_BLOCKS`.
+    YamlStyleMetadataBlocks,
+    /// `ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS`.
+    PlusesDelimitedMetadataBlocks,
+    /// `ENABLE_OLD_FOOTNOTES`.
+    OldFootnotes,
+    /// `ENABLE_MATH`.
+    Math,
+    /// `ENABLE_GFM`.
+    Gfm,
+    /// `ENABLE_DEFINITION_LIST`.
+    DefinitionList,
+    /// `ENABLE_SUPERSCRIPT`.
+    Superscript,
+    /// `ENABLE_SUBSCRIPT`.
+    Subscript,
+    /// `ENABLE_WIKILINKS`.
+    Wikilinks,
+    // Future extensions can be added here.
+}
+
+impl MarkdownExtension {
+    /// The extensions enabled if `markdown-extensions` is not set.
+    pub const DEFAULT: &'static [Self] = &[
+        Self::Tables,
+        Self::Footnotes,
+        Self::Strikethrough,
+        Self::Tasklists,
+        Self::HeadingAttributes,
+        Self::Math,
+        Self::Gfm,
+        Self::DefinitionList,
+        Self::Superscript,
+        Self::Subscript,
+    ];
+
+    /// The corresponding flag of `pulldown-cmark`.
+    pub(crate) const fn flag(self) -> pulldown_cmark::Options {
+        use pulldown_cmark::Options;
+        match self {
+            Self::Tables => Options::ENABLE_TABLES,
+            Self::Footnotes => Options::ENABLE_FOOTNOTES,
+            Self::Strikethrough => Options::ENABLE_STRIKETHROUGH,
+            Self::Tasklists => Options::ENABLE_TASKLISTS,
+            Self::SmartPunctuation => Options::ENABLE_SMART_PUNCTUATION,
+            Self::HeadingAttributes => Options::ENABLE_HEADING_ATTRIBUTES,
+            Self::YamlStyleMetadataBlocks => Options::ENABLE_YAML_STYLE_METADATA_BLOCKS,
+            Self::PlusesDelimitedMetadataBlocks => Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS,
+            Self::OldFootnotes => Options::ENABLE_OLD_FOOTNOTES,
+            Self::Math => Options::ENABLE_MATH,
+            Self::Gfm => Options::ENABLE_GFM,
+            Self::DefinitionList => Options::ENABLE_DEFINITION_LIST,
+            Self::Superscript => Options::ENABLE_SUPERSCRIPT,
+            Self::Subscript => Options::ENABLE_SUBSCRIPT,
+            Self::Wikilinks => Options::ENABLE_WIKILINKS,
+        }
+    }
+
+    /// Settings in `[output.html]` that toggle the extension used by mdBook
+    /// when rendering, with their defaults in mdBook.
+    pub(crate) const HTML_SETTINGS: [(&'static str, Self, bool); 3] = [
+        ("smart-punctuation", Self::SmartPunctuation, true),
+        ("definition-lists", Self::DefinitionList, true),
+        ("admonitions", Self::Gfm, true),
+    ];
+}
+
+/// What the `mdbook-numbering` preprocessor does when a chapter cannot be numbered.
+///
+/// Should be placed under the `on-chapter-error` field
+/// in the `[preprocessor.numbering]` section in `book.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub enum ChapterErrorPolicy {
+    /// Fail the build with an error naming the chapter.
+    ///
+    /// This is the default behavior of `mdbook-numbering`.
+    Fail,
+    /// Warn and leave the chapter unmodified, i.e. without numbers.
+    Skip,
+    // Future policies can be added here.
+}
+
+impl ChapterErrorPolicy {
+    /// Create a new `ChapterErrorPolicy` with default value.
+    pub const fn new() -> Self {
+        Self::Fail
+    }
+}
+
+impl Default for ChapterErrorPolicy {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// How chapters are rewritten by the `mdbook-numbering` preprocessor.
+///
+/// Should be placed under the `rewrite` field
+/// in the `[preprocessor.numbering]` section in `book.toml`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+#[serde(deny_unknown_fields)]
+pub enum RewriteMode {
+    /// Parse the chapter and serialize it to Markdown again with the numbers added.
+    ///
+    /// The Markdown is normalized in the process, e.g. list markers, escapes and spacing
+    /// may change. This is the default behavior of `mdbook-numbering`.
+    Serialize,
+    /// Insert the numbers into the original Markdown of the chapter
+    /// at the positions found by the parser, leaving every other byte untouched.
+    ///
+    /// Code blocks that are replaced with HTML for line numbering are replaced in place.
+    Splice,
+    // Future rewrite modes can be added here.
+}
+
+impl RewriteMode {
+    /// Create a new `RewriteMode` with default value.
+    pub const fn new() -> Self {
+        Self::Serialize
+    }
+}
+
+impl Default for RewriteMode {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
 /// Configuration for the `mdbook-numbering` preprocessor.
 ///
 /// Should be placed under the `[preprocessor.numbering]` section in `book.toml`.
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 #[non_exhaustive]
 #[serde(rename_all = "kebab-case")]
 #[serde(deny_unknown_fields)]
 pub struct NumberingConfig {
     /// Those preprocessors that `mdbook-numbering` should run after.
-    #[serde(default, skip_serializing)]
-    pub after: IgnoredAny,
+    ///
+    /// A warning is shown if `mdbook-numbering` runs before `links`, `katex`,
+    /// `admonish` or `toc` preprocessors, as it depends on their output.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub after: Vec<String>,
+    /// Configuration for the CSS and JavaScript assets.
+    #[serde(default)]
+    pub assets: AssetsConfig,
     /// Those preprocessors that `mdbook-numbering` should run before.
-    #[serde(default, skip_serializing)]
-    pub before: IgnoredAny,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub before: Vec<String>,
     /// Configuration for line numbering in code blocks.
     #[serde(default)]
     pub code: CodeConfig,
     /// Placeholder to ignore unused fields.
     #[serde(default, skip_serializing)]
     pub command: IgnoredAny,
+    /// Whether to fail the build if any warning is found,
+    /// e.g. a heading level that does not fit the chapter numbering.
+    #[serde(default)]
+    pub deny_warnings: bool,
+    /// Configuration for machine-readable diagnostics.
+    #[serde(default)]
+    pub diagnostics: DiagnosticsConfig,
     /// Configuration for heading numbering.
     #[serde(default)]
     pub heading: HeadingConfig,
+    /// Whether to warn and use the default configuration if the configuration is invalid,
+    /// instead of failing the build.
+    #[serde(default)]
+    pub ignore_config_errors: bool,
+    /// Markdown extensions of `pulldown-cmark` to enable when parsing chapters.
+    ///
+    /// If not set, [`MarkdownExtension::DEFAULT`] are enabled. For the `html` renderer,
+    /// or if the `[output.html]` section exists, they are toggled by `smart-punctuation`,
+    /// `definition-lists` and `admonitions` in that section, which default to `true`
+    /// as in mdBook.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub markdown_extensions: Option<Vec<MarkdownExtension>>,
+    /// What to do when a chapter cannot be numbered.
+    #[serde(default)]
+    pub on_chapter_error: ChapterErrorPolicy,
     /// Placeholder to ignore unused fields.
     #[serde(default, skip_serializing)]
     pub optional: IgnoredAny,
-    /// Placeholder to ignore unused fields.
+    /// File to write the [`Outline`](crate::Outline) of the book to as JSON,
+    /// relative to the book root, e.g. `numbering.json`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub outline_json: Option<PathBuf>,
+    /// Per-renderer overrides of the other fields, keyed by renderer name,
+    /// e.g. `[preprocessor.numbering.renderer.pdf]`.
+    ///
+    /// The overrides for the current renderer are merged into
+    /// `[preprocessor.numbering]` before it is deserialized.
     #[serde(default, skip_serializing)]
-    pub renderers: IgnoredAny,
+    pub renderer: IgnoredAny,
+    /// Renderers that `mdbook-numbering` should run for, e.g. `["html"]`.
+    ///
+    /// All renderers are supported if not set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub renderers: Option<Vec<String>>,
+    /// How chapters are rewritten with the numbers added.
+    #[serde(default)]
+    pub rewrite: RewriteMode,
+    /// Whether to parse each rewritten chapter again and warn if it differs from the original
+    /// chapter, besides the inserted numbers.
+    #[serde(default)]
+    pub verify_roundtrip: bool,
     // Future configuration options can be added here.
 }
 
@@ -151,15 +707,138 @@ impl NumberingConfig {
     /// Create a new `NumberingConfig` with default values.
     pub const fn new() -> Self {
         Self {
-            after: IgnoredAny,
-            before: IgnoredAny,
+            after: Vec::new(),
+            assets: AssetsConfig::new(),
+            before: Vec::new(),
             code: CodeConfig::new(),
             command: IgnoredAny,
+            deny_warnings: false,
+            diagnostics: DiagnosticsConfig::new(),
             heading: HeadingConfig::new(),
+            ignore_config_errors: false,
+            markdown_extensions: None,
+            on_chapter_error: ChapterErrorPolicy::new(),
             optional: IgnoredAny,
-            renderers: IgnoredAny,
+            outline_json: None,
+            renderer: IgnoredAny,
+            renderers: None,
+            rewrite: RewriteMode::new(),
+            verify_roundtrip: false,
         }
     }
+
+    /// Set [`after`](Self::after).
+    pub fn after(mut self, after: Vec<String>) -> Self {
+        self.after = after;
+        self
+    }
+
+    /// Set [`assets`](Self::assets).
+    pub fn assets(mut self, assets: AssetsConfig) -> Self {
+        self.assets = assets;
+        self
+    }
+
+    /// Set [`before`](Self::before).
+    pub fn before(mut self, before: Vec<String>) -> Self {
+        self.before = before;
+        self
+    }
+
+    /// Set [`code`](Self::code).
+    pub fn code(mut self, code: CodeConfig) -> Self {
+        self.code = code;
+        self
+    }
+
+    /// Set [`deny_warnings`](Self::deny_warnings).
+    pub fn deny_warnings(mut self, deny_warnings: bool) -> Self {
+        self.deny_warnings = deny_warnings;
+        self
+    }
+
+    /// Set [`diagnostics`](Self::diagnostics).
+    pub fn diagnostics(mut self, diagnostics: DiagnosticsConfig) -> Self {
+        self.diagnostics = diagnostics;
+        self
+    }
+
+    /// Set [`heading`](Self::heading).
+    pub fn heading(mut self, heading: HeadingConfig) -> Self {
+        self.heading = heading;
+        self
+    }
+
+    /// Set [`ignore_config_errors`](Self::ignore_config_errors).
+    pub fn ignore_config_errors(mut self, ignore_config_errors: bool) -> Self {
+        self.ignore_config_errors = ignore_config_errors;
+        self
+    }
+
+    /// Set [`markdown_extensions`](Self::markdown_extensions).
+    pub fn markdown_extensions(mut self, markdown_extensions: Vec<MarkdownExtension>) -> Self {
+        self.markdown_extensions = Some(markdown_extensions);
+        self
+    }
+
+    /// Set [`on_chapter_error`](Self::on_chapter_error).
+    pub fn on_chapter_error(mut self, on_chapter_error: ChapterErrorPolicy) -> Self {
+        self.on_chapter_error = on_chapter_error;
+        self
+    }
+
+    /// Set [`outline_json`](Self::outline_json).
+    pub fn outline_json(mut self, outline_json: impl Into<PathBuf>) -> Self {
+        self.outline_json = Some(outline_json.into());
+        self
+    }
+
+    /// Set [`renderers`](Self::renderers).
+    pub fn renderers(mut self, renderers: Vec<String>) -> Self {
+        self.renderers = Some(renderers);
+        self
+    }
+
+    /// Set [`rewrite`](Self::rewrite).
+    pub fn rewrite(mut self, rewrite: RewriteMode) -> Self {
+        self.rewrite = rewrite;
+        self
+    }
+
+    /// Set [`verify_roundtrip`](Self::verify_roundtrip).
+    pub fn verify_roundtrip(mut self, verify_roundtrip: bool) -> Self {
+        self.verify_roundtrip = verify_roundtrip;
+        self
+    }
+
+    /// Parse the configuration from the content of the `[preprocessor.numbering]` section
+    /// of `book.toml`, e.g. `heading.numbering-style = "top"`.
+    ///
+    /// Unlike when running as a preprocessor, [`markdown_extensions`](Self::markdown_extensions)
+    /// is not derived from the `[output.html]` section, and per-renderer overrides
+    /// in `renderer` are ignored.
+    ///
+    /// # Errors
+    ///
+    /// Returns an error if the TOML is invalid or does not match the configuration,
+    /// listing the valid keys or values, with a suggestion if possible.
+    pub fn from_toml_str(toml: &str) -> Result<Self, Error> {
+        // Deserializing from a `Value` names the full key of a mismatched value.
+        toml::from_str::<toml::Value>(toml)
+            .and_then(toml::Value::try_into)
+            .context("Failed to parse the configuration")
+            .map_err(explain_error)
+    }
+}
+
+impl NumberingConfig {
+    /// Whether `mdbook-numbering` should run for the renderer named `renderer`,
+    /// according to [`NumberingConfig::renderers`].
+    pub fn supports_renderer(&self, renderer: &str) -> bool {
+        self.renderers
+            .as_ref()
+            .is_none_or(|renderers| renderers.iter().any(|name| name == renderer))
+    }
 }
 
 impl Default for NumberingConfig {
@@ -170,7 +849,108 @@ impl Default for NumberingConfig {
 
 impl PartialEq for NumberingConfig {
     fn eq(&self, other: &Self) -> bool {
-        self.code == other.code && self.heading == other.heading
+        self.after == other.after
+            && self.assets == other.assets
+            && self.before == other.before
+            && self.code == other.code
+            && self.deny_warnings == other.deny_warnings
+            && self.diagnostics == other.diagnostics
+            && self.heading == other.heading
+            && self.ignore_config_errors == other.ignore_config_errors
+            && self.markdown_extensions == other.markdown_extensions
+            && self.on_chapter_error == other.on_chapter_error
+            && self.outline_json == other.outline_json
+            && self.renderers == other.renderers
+            && self.rewrite == other.rewrite
+            && self.verify_roundtrip == other.verify_roundtrip
     }
 }
 impl Eq for NumberingConfig {}
+
+/// Merge `overrides` into `base`.
+///
+/// Tables are merged key by key recursively, while any other value
+/// in `overrides` replaces the one in `base`.
+pub(crate) fn merge_table(base: &mut toml::value::Table, overrides: toml::value::Table) {
+    for (key, value) in overrides {
+        match (base.get_mut(&key), value) {
+            (Some(toml::Value::Table(base)), toml::Value::Table(overrides)) => {
+                merge_table(base, overrides)
+            }
+            (_, value) => {
+                base.insert(key, value);
+            }
+        }
+    }
+}
+
+/// Flatten a configuration error into a single message with its cause,
+/// which lists the valid keys or values,
+/// and suggest the closest valid one for an unknown key or value.
+pub(crate) fn explain_error(err: Error) -> Error {
+    let cause = err.root_cause().to_string();
+    if err.chain().count() < 2 {
+        return err;
+    }
+    let cause = cause.split_whitespace().collect::<Vec<_>>().join(" ");
+    match suggest(&cause) {
+        Some(suggestion) => anyhow!("{err}: {cause}. Did you mean `{suggestion}`?"),
+        None => anyhow!("{err}: {cause}."),
+    }
+}
+
+/// Find the expected key or value that is closest to the unknown one in
+/// a `serde` error message like ``unknown field `a`, expected one of `b`, `c` ``.
+fn suggest(message: &str) -> Option<&str> {
+    fn normalize(name: &str) -> String {
+        name.to_lowercase().replace('_', "-")
+    }
+
+    let (_, rest) = message
+        .split_once("unknown field `")
+        .or_else(|| message.split_once("unknown variant `"))?;
+    let (unknown, rest) = rest.split_once('`')?;
+    let (_, expected) = rest.split_once("expected")?;
+    let unknown = normalize(unknown);
+    // Candidates are quoted with backticks and separated by `, ` or ` or `,
+    // which is followed by other text like `` in `heading` ``.
+    let mut parts = expected.split('`').skip(1);
+    std::iter::from_fn(|| {
+        let candidate = parts.next()?;
+        let separator = parts.next().unwrap_or_default();
+        Some((candidate, separator))
+    })
+    .scan(true, |more, (candidate, separator)| {
+        let item = more.then_some(candidate);
+        *more = matches!(separator, ", " | " or " | ", or ");
+        item
+    })
+    .map(|candidate| (edit_distance(&unknown, &normalize(candidate)), candidate))
+    .filter(|&(distance, _)| distance <= (unknown.chars().count() / 3).max(1))
+    .min_by_key(|&(distance, _)| distance)
+    .map(|(_, candidate)| candidate)
+}
+
+/// The edit distance between `a` and `b`, counting insertions, deletions,
+/// substitutions and transpositions of adjacent characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let a: Vec<char> = a.chars().collect();
+    let b: Vec<char> = b.chars().collect();
+    let mut d = vec![vec![0; b.len() + 1]; a.len() + 1];
+    for (i, row) in d.iter_mut().enumerate() {
+        row[0] = i;
+    }
+    d[0] = (0..=b.len()).collect();
+    for i in 1..=a.len() {
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            d[i][j] = (d[i - 1][j] + 1)
+                .min(d[i][j - 1] + 1)
+                .min(d[i - 1][j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                d[i][j] = d[i][j].min(d[i - 2][j - 2] + 1);
+            }
+        }
+    }
+    d[a.len()][b.len()]
+}
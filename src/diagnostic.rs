@@ -0,0 +1,209 @@
+use std::fmt::{self, Display};
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use mdbook_preprocessor::book::SectionNumber;
+use serde::Serialize;
+
+/// Kinds of diagnostics reported by `mdbook-numbering`, each with a stable code.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum DiagnosticKind {
+    /// A heading is more than one level deeper than the previous one.
+    HeadingTooDeep,
+    /// A heading is shallower than the chapter numbering.
+    HeadingTooShallow,
+    /// `ln-continue=<id>` refers to a code block that does not exist.
+    UnknownCodeBlockId,
+    /// An asset file is not listed in `output.html`.
+    AssetNotReferenced,
+    /// `mdbook-numbering` runs before a preprocessor it depends on.
+    PreprocessorOrder,
+    /// A chapter is changed by rewriting it, besides the inserted numbers.
+    RoundtripMismatch,
+    /// A chapter cannot be numbered, and is left unmodified.
+    ChapterSkipped,
+    /// A [`NumberFormatter`](crate::NumberFormatter) returned an attribute value
+    /// that cannot be written in a heading attribute block.
+    InvalidNumberAttribute,
+}
+
+impl DiagnosticKind {
+    /// The stable code, e.g. `N001`.
+    pub const fn code(self) -> &'static str {
+        match self {
+            Self::HeadingTooDeep => "N001",
+            Self::HeadingTooShallow => "N002",
+            Self::UnknownCodeBlockId => "N003",
+            Self::AssetNotReferenced => "N004",
+            Self::PreprocessorOrder => "N005",
+            Self::RoundtripMismatch => "N006",
+            Self::ChapterSkipped => "N007",
+            Self::InvalidNumberAttribute => "N008",
+        }
+    }
+
+    /// The stable name, e.g. `heading-too-deep`.
+    pub const fn name(self) -> &'static str {
+        match self {
+            Self::HeadingTooDeep => "heading-too-deep",
+            Self::HeadingTooShallow => "heading-too-shallow",
+            Self::UnknownCodeBlockId => "unknown-code-block-id",
+            Self::AssetNotReferenced => "asset-not-referenced",
+            Self::PreprocessorOrder => "preprocessor-order",
+            Self::RoundtripMismatch => "roundtrip-mismatch",
+            Self::ChapterSkipped => "chapter-skipped",
+            Self::InvalidNumberAttribute => "invalid-number-attribute",
+        }
+    }
+}
+
+/// How severe a diagnostic is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub(crate) enum Severity {
+    /// The build goes on.
+    Warning,
+    /// The build fails, as warnings are denied.
+    Error,
+}
+
+/// A problem found by `mdbook-numbering`.
+///
+/// It is displayed as the message followed by the location, if any,
+/// in the same format as `rustc` does.
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub struct Diagnostic {
+    /// The kind of the problem.
+    pub kind: DiagnosticKind,
+    /// The human-readable message, without the location.
+    pub message: String,
+    /// Path of the source file, relative to the book root.
+    pub file: Option<PathBuf>,
+    /// 1-based line and column in the source file.
+    ///
+    /// They are counted in the content of the chapter after `{{#include}}` and similar
+    /// links are expanded, so they are off in the lines after such links.
+    pub position: Option<(usize, usize)>,
+    /// Number of the chapter, e.g. `1.2.`.
+    pub chapter: Option<String>,
+}
+
+impl Diagnostic {
+    pub(crate) fn new(kind: DiagnosticKind, message: impl Display) -> Self {
+        Self {
+            kind,
+            message: message.to_string(),
+            file: None,
+            position: None,
+            chapter: None,
+        }
+    }
+
+    /// Serialize the diagnostic as a single line of JSON.
+    pub(crate) fn to_json(&self, severity: Severity) -> String {
+        #[derive(Serialize)]
+        struct Json<'a> {
+            severity: Severity,
+            code: &'static str,
+            name: &'static str,
+            message: &'a str,
+            file: Option<&'a Path>,
+            line: Option<usize>,
+            column: Option<usize>,
+            chapter: Option<&'a str>,
+        }
+
+        let json = Json {
+            severity,
+            code: self.kind.code(),
+            name: self.kind.name(),
+            message: &self.message,
+            file: self.file.as_deref(),
+            line: self.position.map(|(line, _)| line),
+            column: self.position.map(|(_, column)| column),
+            chapter: self.chapter.as_deref(),
+        };
+        serde_json::to_string(&json).unwrap()
+    }
+}
+
+impl Display for Diagnostic {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(&self.message)?;
+        if let Some(file) = &self.file {
+            write!(f, "\n  --> {}", file.display())?;
+            if let Some((line, column)) = self.position {
+                write!(f, ":{line}:{column}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for Diagnostic {}
+
+/// Write `lines` of JSON diagnostics to `path` relative to `root`,
+/// or to stderr if `path` is `-`.
+pub(crate) fn write_json(path: &Path, root: &Path, lines: &[String]) -> io::Result<()> {
+    if path == Path::new("-") {
+        for line in lines {
+            eprintln!("{line}");
+        }
+        return Ok(());
+    }
+    let mut content = String::new();
+    for line in lines {
+        content.push_str(line);
+        content.push('\n');
+    }
+    fs::write(root.join(path), content)
+}
+
+/// The source of a chapter, used to point diagnostics at the offending Markdown.
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Source<'a> {
+    /// Path of the source file, relative to the book root.
+    path: Option<&'a Path>,
+    content: &'a str,
+    number: Option<&'a SectionNumber>,
+}
+
+impl<'a> Source<'a> {
+    pub(crate) const fn new(
+        path: Option<&'a Path>,
+        content: &'a str,
+        number: Option<&'a SectionNumber>,
+    ) -> Self {
+        Self {
+            path,
+            content,
+            number,
+        }
+    }
+
+    /// The 1-based line and column of the byte `offset` in the content.
+    ///
+    /// Columns are counted in characters, as editors do. The content has links like
+    /// `{{#include}}` expanded, so the result only matches the source file up to the first one.
+    pub(crate) fn line_column(&self, offset: usize) -> (usize, usize) {
+        let before = &self.content[..offset];
+        let line_start = before.rfind('\n').map_or(0, |i| i + 1);
+        let line = before.matches('\n').count() + 1;
+        let column = before[line_start..].chars().count() + 1;
+        (line, column)
+    }
+
+    /// Attach the location of the byte `offset` and the chapter number to `diagnostic`.
+    ///
+    /// The location is left out if the path of the source file is unknown.
+    pub(crate) fn locate(&self, offset: usize, mut diagnostic: Diagnostic) -> Diagnostic {
+        if let Some(path) = self.path {
+            diagnostic.file = Some(path.to_owned());
+            diagnostic.position = Some(self.line_column(offset));
+        }
+        diagnostic.chapter = self.number.map(ToString::to_string);
+        diagnostic
+    }
+}
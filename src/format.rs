@@ -0,0 +1,78 @@
+use std::path::Path;
+
+use mdbook_preprocessor::book::SectionNumber;
+
+/// A heading to be numbered, as passed to [`NumberFormatter::format`].
+#[derive(Debug, Clone, Copy)]
+#[non_exhaustive]
+pub struct HeadingContext<'a> {
+    /// The counters of the heading, e.g. `1.2.3.` for the third heading of level 3
+    /// in the second section of chapter 1, with [`NumberingStyle::Consecutive`].
+    ///
+    /// [`NumberingStyle::Consecutive`]: crate::NumberingStyle::Consecutive
+    pub number: &'a SectionNumber,
+    /// The level of the heading, from 1 for `#` to 6 for `######`.
+    pub level: usize,
+    /// The number of the chapter the heading is in.
+    pub chapter_number: &'a SectionNumber,
+    /// The name of the chapter the heading is in.
+    pub chapter_name: &'a str,
+    /// The path of the source file of the chapter, relative to the source directory.
+    pub chapter_path: Option<&'a Path>,
+}
+
+/// The number of a heading as rendered, returned by [`NumberFormatter::format`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct FormattedNumber {
+    /// The label inserted before the heading text, followed by a space,
+    /// e.g. in the `<span class="heading numbering">` element.
+    pub label: String,
+    /// The value of the `data-numbering` attribute of the heading.
+    ///
+    /// It should not contain whitespace or braces, as it is written in a Markdown
+    /// heading attribute block. Otherwise, the counters of the heading are used instead,
+    /// and a [`DiagnosticKind::InvalidNumberAttribute`](crate::DiagnosticKind) is reported.
+    pub attribute: String,
+}
+
+impl FormattedNumber {
+    /// Create a new `FormattedNumber` with the given label and attribute value.
+    pub fn new(label: impl Into<String>, attribute: impl Into<String>) -> Self {
+        Self {
+            label: label.into(),
+            attribute: attribute.into(),
+        }
+    }
+}
+
+/// Formats the numbers of headings.
+///
+/// Implement it to render heading numbers differently, e.g. with letters or Roman numerals,
+/// and pass it to [`NumberingPreprocessor::with_formatter`].
+///
+/// [`NumberingPreprocessor::with_formatter`]: crate::NumberingPreprocessor::with_formatter
+pub trait NumberFormatter: Send + Sync {
+    /// Format the number of `heading`.
+    fn format(&self, heading: &HeadingContext<'_>) -> FormattedNumber;
+}
+
+/// The default [`NumberFormatter`], which displays the counters as a [`SectionNumber`],
+/// e.g. `1.2.3.`, for both the label and the attribute value.
+#[derive(Debug, Clone, Copy, Default)]
+#[non_exhaustive]
+pub struct SectionNumberFormatter;
+
+impl SectionNumberFormatter {
+    /// Create a new `SectionNumberFormatter`.
+    pub const fn new() -> Self {
+        Self
+    }
+}
+
+impl NumberFormatter for SectionNumberFormatter {
+    fn format(&self, heading: &HeadingContext<'_>) -> FormattedNumber {
+        let number = heading.number.to_string();
+        FormattedNumber::new(number.clone(), number)
+    }
+}
@@ -0,0 +1,357 @@
+use std::path::Path;
+
+use mdbook_preprocessor::book::SectionNumber;
+use pulldown_cmark::{CowStr, Event, HeadingLevel, Tag};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+use crate::{
+    FormattedNumber, HeadingConfig, HeadingContext, HeadingRender, NumberFormatter, NumberingStyle,
+    OutputFormat, SectionNumberFormatter, code, splice,
+};
+
+/// Numbers of the headings in a numbered chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct HeadingCounter<'a> {
+    /// Number of the chapter.
+    chapter: &'a SectionNumber,
+    /// Number of the last heading.
+    stack: SectionNumber,
+    style: NumberingStyle,
+    /// Name of the chapter, used in warnings.
+    name: &'a str,
+}
+
+impl<'a> HeadingCounter<'a> {
+    pub(crate) fn new(chapter: &'a SectionNumber, style: NumberingStyle, name: &'a str) -> Self {
+        Self {
+            chapter,
+            stack: chapter.clone(),
+            style,
+            name,
+        }
+    }
+
+    /// Advance to the next heading, which is of `level`, and return its number.
+    ///
+    /// Heading levels that do not fit the chapter numbering are reported to `warn`.
+    pub(crate) fn next(
+        &mut self,
+        level: HeadingLevel,
+        mut warn: impl FnMut(Diagnostic),
+    ) -> &SectionNumber {
+        let Self {
+            chapter,
+            stack,
+            style,
+            name,
+        } = self;
+        let level_depth = match style {
+            NumberingStyle::Consecutive => level as usize,
+            NumberingStyle::Top => level as usize + chapter.len() - 1,
+        };
+        if level_depth > stack.len() + 1 {
+            let message = format!(
+                "\
+                Heading level {} found, \
+                but only {} levels in numbering \"{}\" for chapter \"{}\".",
+                level,
+                stack.len(),
+                stack,
+                name,
+            );
+            warn(Diagnostic::new(DiagnosticKind::HeadingTooDeep, message));
+        }
+        if *style == NumberingStyle::Consecutive && level_depth < chapter.len() {
+            let message = format!(
+                "\
+                Heading level {} found, \
+                but numbering \"{}\" for chapter \"{}\" has more levels. \
+                Consider using `numbering-style = \"top\"` in the config, \
+                if you want the top heading to be level 1.",
+                level, stack, name,
+            );
+            warn(Diagnostic::new(DiagnosticKind::HeadingTooShallow, message));
+        }
+        while level_depth > stack.len() {
+            stack.push(0);
+        }
+        stack.truncate(level_depth);
+        if level_depth > chapter.len() {
+            stack[level_depth - 1] += 1;
+        }
+        stack
+    }
+}
+
+/// The `<span>` element inserted before the content of a heading numbered with `label`.
+///
+/// The content of the element is still parsed as Markdown, so punctuation in `label`
+/// is escaped as in [`splice::escape_markdown`], unless it is escaped as HTML.
+pub(crate) fn span(label: &str) -> String {
+    let mut html = String::from("<span class=\"heading numbering\">");
+    for c in label.chars() {
+        match c {
+            '&' | '<' | '>' | '"' => code::escape_html(c.encode_utf8(&mut [0; 4]), &mut html),
+            _ => html.push_str(&splice::escape_markdown(c.encode_utf8(&mut [0; 4]), false)),
+        }
+    }
+    html.push_str(" </span>");
+    html
+}
+
+/// Whether `attribute` can be written as the value of `data-numbering=` in a heading
+/// attribute block, which ends at whitespace or a brace.
+fn is_valid_attribute(attribute: &str) -> bool {
+    !attribute.contains(|c: char| c.is_whitespace() || c == '{' || c == '}')
+}
+
+/// Numbers the headings of a chapter and renders their numbers.
+pub(crate) struct HeadingNumberer<'a> {
+    counter: Option<HeadingCounter<'a>>,
+    /// Number of the chapter, or `None` if it is not numbered.
+    chapter_number: Option<&'a SectionNumber>,
+    chapter_name: &'a str,
+    chapter_path: Option<&'a Path>,
+    formatter: &'a dyn NumberFormatter,
+    config: HeadingConfig,
+    format: OutputFormat,
+    /// Labels inserted as text, to be removed when verifying the output.
+    pub(crate) labels: Vec<String>,
+}
+
+impl<'a> HeadingNumberer<'a> {
+    pub(crate) fn new(
+        chapter_number: Option<&'a SectionNumber>,
+        config: &HeadingConfig,
+        format: OutputFormat,
+    ) -> Self {
+        Self {
+            counter: chapter_number.map(|a| HeadingCounter::new(a, config.numbering_style, "")),
+            chapter_number,
+            chapter_name: "",
+            chapter_path: None,
+            formatter: &SectionNumberFormatter,
+            config: *config,
+            format,
+            labels: Vec::new(),
+        }
+    }
+
+    /// Set the name of the chapter, used in warnings and passed to the formatter.
+    pub(crate) fn chapter_name(&mut self, name: &'a str) {
+        self.chapter_name = name;
+        if let Some(counter) = &mut self.counter {
+            counter.name = name;
+        }
+    }
+
+    pub(crate) fn chapter_path(&mut self, path: Option<&'a Path>) {
+        self.chapter_path = path;
+    }
+
+    pub(crate) fn formatter(&mut self, formatter: &'a dyn NumberFormatter) {
+        self.formatter = formatter;
+    }
+
+    /// Whether heading numbers are inserted at all.
+    pub(crate) const fn enabled(&self) -> bool {
+        self.config.enable
+    }
+
+    /// Whether numbered headings get a `<span>` element.
+    pub(crate) fn renders_span(&self) -> bool {
+        self.format == OutputFormat::Html && self.config.render == HeadingRender::Span
+    }
+
+    /// The number of the next heading, which is of `level`,
+    /// or `None` if the chapter is not numbered.
+    ///
+    /// Heading levels that do not fit the chapter numbering are reported to `warn`.
+    pub(crate) fn number(
+        &mut self,
+        level: HeadingLevel,
+        warn: impl FnMut(Diagnostic),
+    ) -> Option<FormattedNumber> {
+        let (_, number) = self.number_with_counters(level, warn)?;
+        if self.format == OutputFormat::Text {
+            self.labels.push(number.label.clone());
+        }
+        Some(number)
+    }
+
+    /// Like [`number`](Self::number), but also return the counters of the heading.
+    ///
+    /// Attribute values returned by the formatter that cannot be written in a heading
+    /// attribute block are reported to `warn`, and replaced with the counters of the heading.
+    pub(crate) fn number_with_counters(
+        &mut self,
+        level: HeadingLevel,
+        mut warn: impl FnMut(Diagnostic),
+    ) -> Option<(SectionNumber, FormattedNumber)> {
+        let number = self.counter.as_mut()?.next(level, &mut warn);
+        let mut formatted = self.formatter.format(&HeadingContext {
+            number,
+            level: level as usize,
+            chapter_number: self.chapter_number?,
+            chapter_name: self.chapter_name,
+            chapter_path: self.chapter_path,
+        });
+        if !is_valid_attribute(&formatted.attribute) {
+            let message = format!(
+                "\
+                Heading number attribute \"{}\" in chapter \"{}\" contains whitespace or braces, \
+                using \"{}\" instead.",
+                formatted.attribute, self.chapter_name, number,
+            );
+            warn(Diagnostic::new(
+                DiagnosticKind::InvalidNumberAttribute,
+                message,
+            ));
+            formatted.attribute = number.to_string();
+        }
+        Some((number.clone(), formatted))
+    }
+
+    /// Number `event` if it starts a heading, adding a `data-numbering` attribute to it
+    /// in HTML output, and return the event to insert after it, if any.
+    pub(crate) fn number_event<'e>(
+        &mut self,
+        event: &mut Event<'e>,
+        warn: impl FnMut(Diagnostic),
+    ) -> Option<Event<'e>> {
+        let Event::Start(Tag::Heading { level, attrs, .. }) = event else {
+            return None;
+        };
+        if !self.config.enable {
+            return None;
+        }
+        let number = self.number(*level, warn);
+        match self.format {
+            OutputFormat::Text => Some(Event::Text(CowStr::from(format!("{} ", number?.label)))),
+            OutputFormat::Html => {
+                let value = number.as_ref().map(|n| CowStr::from(n.attribute.clone()));
+                attrs.push((CowStr::from("data-numbering"), value));
+                let number = number.filter(|_| self.config.render == HeadingRender::Span)?;
+                Some(Event::InlineHtml(CowStr::from(span(&number.label))))
+            }
+        }
+    }
+}
+
+/// An iterator adapter numbering the headings in a stream of [`pulldown_cmark`] events,
+/// as `mdbook-numbering` does for the HTML renderer.
+///
+/// Numbered headings get a `data-numbering` attribute, and a `<span>` element
+/// holding the number if [`HeadingConfig::render`] is [`HeadingRender::Span`].
+/// Headings are left unmodified if [`HeadingConfig::enable`] is `false`.
+///
+/// Code blocks are not numbered, and no assets are added, so the heading numbers
+/// are only styled if the assets of `mdbook-numbering` are included in the book.
+///
+/// The events are those of the [`pulldown_cmark`](crate::pulldown_cmark) version
+/// re-exported by this crate.
+///
+/// The example serializes the events to Markdown, where the attributes are written
+/// in `{ ... }` blocks. So whoever parses that Markdown again, like mdBook does,
+/// must enable `ENABLE_HEADING_ATTRIBUTES`, or the blocks end up in the heading text.
+///
+/// ```
+/// use mdbook_numbering::pulldown_cmark::Parser;
+/// use mdbook_numbering::{HeadingConfig, NumberHeadings};
+/// use mdbook_preprocessor::book::SectionNumber;
+///
+/// let number = SectionNumber::new(vec![2]);
+/// let config = HeadingConfig::new();
+/// let parser = Parser::new("# Title\n\n## Section");
+/// let mut events = NumberHeadings::new(parser, Some(&number), &config);
+/// let mut markdown = String::new();
+/// pulldown_cmark_to_cmark::cmark(events.by_ref(), &mut markdown)?;
+/// assert!(events.diagnostics().is_empty());
+/// assert_eq!(
+///     markdown,
+///     "# <span class=\"heading numbering\">2. </span>Title { data-numbering=2. }\n\n## \
+///      <span class=\"heading numbering\">2.1. </span>Section { data-numbering=2.1. }",
+/// );
+/// # Ok::<(), pulldown_cmark_to_cmark::Error>(())
+/// ```
+pub struct NumberHeadings<'a, I: Iterator> {
+    events: I,
+    numberer: HeadingNumberer<'a>,
+    /// Event to yield before the next one of `events`.
+    pending: Option<I::Item>,
+    diagnostics: Vec<Diagnostic>,
+}
+
+impl<'a, 'e, I: Iterator<Item = Event<'e>>> NumberHeadings<'a, I> {
+    /// Number the headings in `events`, of the chapter numbered `chapter_number`,
+    /// or of an unnumbered chapter if it is `None`.
+    ///
+    /// The headings of an unnumbered chapter only get an empty `data-numbering` attribute.
+    pub fn new(
+        events: I,
+        chapter_number: Option<&'a SectionNumber>,
+        config: &HeadingConfig,
+    ) -> Self {
+        Self {
+            events,
+            numberer: HeadingNumberer::new(chapter_number, config, OutputFormat::Html),
+            pending: None,
+            diagnostics: Vec::new(),
+        }
+    }
+
+    /// Format the heading numbers with `formatter`
+    /// instead of [`SectionNumberFormatter`].
+    pub fn formatter(mut self, formatter: &'a dyn NumberFormatter) -> Self {
+        self.numberer.formatter(formatter);
+        self
+    }
+
+    /// Set the name of the chapter, used in warnings and passed to the formatter.
+    pub fn chapter_name(mut self, name: &'a str) -> Self {
+        self.numberer.chapter_name(name);
+        self
+    }
+
+    /// Set the path of the source file of the chapter, passed to the formatter.
+    pub fn chapter_path(mut self, path: &'a Path) -> Self {
+        self.numberer.chapter_path(Some(path));
+        self
+    }
+
+    /// Insert the heading numbers as plain text instead of HTML,
+    /// as for renderers other than `html`.
+    pub fn plain_text(mut self, plain_text: bool) -> Self {
+        self.numberer.format = if plain_text {
+            OutputFormat::Text
+        } else {
+            OutputFormat::Html
+        };
+        self
+    }
+
+    /// The warnings found so far, e.g. about headings too deep for the chapter numbering.
+    ///
+    /// Use [`Iterator::by_ref`] to pass the adapter on while keeping access to them.
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+}
+
+impl<'e, I: Iterator<Item = Event<'e>>> Iterator for NumberHeadings<'_, I> {
+    type Item = Event<'e>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if let Some(event) = self.pending.take() {
+            return Some(event);
+        }
+        let mut event = self.events.next()?;
+        let chapter_number = self.numberer.chapter_number;
+        let diagnostics = &mut self.diagnostics;
+        self.pending = self.numberer.number_event(&mut event, |mut warning| {
+            warning.chapter = chapter_number.map(ToString::to_string);
+            diagnostics.push(warning);
+        });
+        Some(event)
+    }
+}
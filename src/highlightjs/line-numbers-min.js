@@ -1,4 +1,4 @@
 (function(W,K){let P=/\r\n|\r|\n/g;if(W.hljs)W.hljs.initLineNumbersOnLoad=V,W.hljs.lineNumbersBlock=y,W.hljs.lineNumbersBlockSync=A,W.hljs.lineNumbersValue=S,B(),V();else W.console.error("highlight.js not detected!");function H(q){var z=q;while(z){if(z.className&&z.className.indexOf("hljs-ln-code")!==-1)return!0;z=z.parentNode}return!1}function O(q){var z=q;while(z.nodeName!=="TABLE")z=z.parentNode;return z}function R(q){var z=q.toString(),J=q.anchorNode;while(J.nodeName!=="TD")J=J.parentNode;var Q=q.focusNode;while(Q.nodeName!=="TD")Q=Q.parentNode;var U=parseInt(J.dataset.lineNumber),Y=parseInt(Q.dataset.lineNumber);if(U!=Y){var Z=J.textContent,$=Q.textContent;if(U>Y){var G=U;U=Y,Y=G,G=Z,Z=$,$=G}while(z.indexOf(Z)!==0)Z=Z.slice(1);while(z.lastIndexOf($)===-1)$=$.slice(0,-1);var M=Z,b=O(J);for(var D=U+1;D<Y;++D){var x=X('.{0}[{1}="{2}"]',["hljs-ln-code","data-line-number",D]),p=b.querySelector(x);M+=`
 `+p.textContent}return M+=`
-`+$,M}else return z}document.addEventListener("copy",function(q){var z=window.getSelection();if(H(z.anchorNode)){var J;if(window.navigator.userAgent.indexOf("Edge")!==-1)J=R(z);else J=z.toString();q.clipboardData.setData("text/plain",J),q.preventDefault()}});function B(){var q=K.createElement("style");q.type="text/css",q.innerHTML=X(".{0}{border-collapse:collapse}.{0} td{padding:0}.{1}:before{content:attr({2})}",["hljs-ln","hljs-ln-n","data-line-number"]),K.getElementsByTagName("head")[0].appendChild(q)}function V(q){if(K.readyState==="interactive"||K.readyState==="complete")_(q);else W.addEventListener("DOMContentLoaded",function(){_(q)})}function _(q){try{var z=K.querySelectorAll("code.hljs,code.nohighlight");for(var J in z)if(z.hasOwnProperty(J)){if(!v(z[J]))y(z[J],q)}}catch(Q){W.console.error("LineNumbers error: ",Q)}}function v(q){return q.classList.contains("nohljsln")}function y(q,z){if(typeof q!=="object")return;N(function(){q.innerHTML=C(q,z)})}function A(q,z){if(typeof q!=="object")return;q.innerHTML=C(q,z)}function S(q,z){if(typeof q!=="string")return;var J=document.createElement("code");return J.innerHTML=q,C(J,z)}function C(q,z){var J=g(q,z);return I(q),F(q.innerHTML,J)}function F(q,z){var J=j(q);if(J[J.length-1].trim()==="")J.pop();if(J.length>1||z.singleLine){var Q="";for(var U=0,Y=J.length;U<Y;U++)Q+=X('<tr class="{0}"><td class="{0} {1}" {3}="{5}"><div class="{2}" {3}="{5}"></div></td><td class="{0} {4}" {3}="{5}">{6}</td></tr>',["hljs-ln-line","hljs-ln-numbers","hljs-ln-n","data-line-number","hljs-ln-code",U+z.startFrom,J[U].length>0?J[U]:" "]);return X('<table class="{0}">{1}</table>',["hljs-ln",Q])}return q}function g(q,z){return z=z||{},{singleLine:w(z),startFrom:k(q,z)}}function w(q){var z=!1;if(q.singleLine)return q.singleLine;return z}function k(q,z){var J=1,Q=J;if(isFinite(z.startFrom))Q=z.startFrom;var U=f(q,"data-ln-start-from");if(U!==null)Q=T(U,J);return Q}function I(q){var z=q.childNodes;for(var J in z)if(z.hasOwnProperty(J)){var Q=z[J];if(E(Q.textContent)>0)if(Q.childNodes.length>0)I(Q);else h(Q.parentNode)}}function h(q){var z=q.className;if(!/hljs-/.test(z))return;var J=j(q.innerHTML);for(var Q=0,U="";Q<J.length;Q++){var Y=J[Q].length>0?J[Q]:" ";U+=X(`<span class="{0}">{1}</span>
+`+$,M}else return z}document.addEventListener("copy",function(q){var z=window.getSelection();if(H(z.anchorNode)){var J;if(window.navigator.userAgent.indexOf("Edge")!==-1)J=R(z);else J=z.toString();q.clipboardData.setData("text/plain",J),q.preventDefault()}});function B(){var q=K.createElement("style");q.type="text/css",q.innerHTML=X(".{0}{border-collapse:collapse}.{0} td{padding:0}.{1}:before{content:attr({2})}",["hljs-ln","hljs-ln-n","data-line-number"]),K.getElementsByTagName("head")[0].appendChild(q)}function V(q){if(K.readyState==="interactive"||K.readyState==="complete")_(q);else W.addEventListener("DOMContentLoaded",function(){_(q)})}function _(q){try{var z=K.querySelectorAll("code.hljs,code.nohighlight");for(var J in z)if(z.hasOwnProperty(J)){if(!v(z[J]))y(z[J],q)}}catch(Q){W.console.error("LineNumbers error: ",Q)}}function v(q){return q.classList.contains("nohljsln")}function y(q,z){if(typeof q!=="object")return;N(function(){q.innerHTML=C(q,z)})}function A(q,z){if(typeof q!=="object")return;q.innerHTML=C(q,z)}function S(q,z){if(typeof q!=="string")return;var J=document.createElement("code");return J.innerHTML=q,C(J,z)}function C(q,z){var J=g(q,z);return I(q),F(q.innerHTML,J)}function F(q,z){var J=j(q);if(J[J.length-1].trim()==="")J.pop();if(J.length>1||z.singleLine){var Q="";for(var U=0,Y=J.length;U<Y;U++)Q+=X('<tr class="{0}"><td class="{0} {1}" {3}="{5}"><div class="{2}" {3}="{5}"></div></td><td class="{0} {4}" {3}="{5}">{6}</td></tr>',["hljs-ln-line","hljs-ln-numbers","hljs-ln-n","data-line-number","hljs-ln-code",U+z.startFrom,J[U].length>0?J[U]:" "]);return X('<table class="{0}">{1}</table>',["hljs-ln",Q])}return q}function g(q,z){return z=z||{},{singleLine:w(z),startFrom:k(q,z)}}function w(q){var z=!1;if(q.singleLine)return q.singleLine;return z}function k(q,z){var J=1,Q=J;if(isFinite(z.startFrom))Q=z.startFrom;var G=/(?:^|\s)(?:language-)?ln-start-from-(\d+)(?:\s|$)/.exec(q.className);if(G!==null)Q=T(G[1],J);var U=f(q,"data-ln-start-from");if(U!==null)Q=T(U,J);return Q}function I(q){var z=q.childNodes;for(var J in z)if(z.hasOwnProperty(J)){var Q=z[J];if(E(Q.textContent)>0)if(Q.childNodes.length>0)I(Q);else h(Q.parentNode)}}function h(q){var z=q.className;if(!/hljs-/.test(z))return;var J=j(q.innerHTML);for(var Q=0,U="";Q<J.length;Q++){var Y=J[Q].length>0?J[Q]:" ";U+=X(`<span class="{0}">{1}</span>
 `,[z,Y])}q.innerHTML=U.trim()}function j(q){if(q.length===0)return[];return q.split(P)}function E(q){return(q.trim().match(P)||[]).length}function N(q){W.setTimeout(q,0)}function X(q,z){return q.replace(/\{(\d+)\}/g,function(J,Q){return z[Q]!==void 0?z[Q]:J})}function f(q,z){return q.hasAttribute(z)?q.getAttribute(z):null}function T(q,z){if(!q)return z;var J=Number(q);return isFinite(J)?J:z}})(window,document);
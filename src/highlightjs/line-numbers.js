@@ -277,6 +277,13 @@
             startFrom = options.startFrom;
         }
 
+        // set by mdbook-numbering from the `ln-start-from-<n>` info string token,
+        // which mdBook turns into a class of the code element
+        var match = /(?:^|\s)(?:language-)?ln-start-from-(\d+)(?:\s|$)/.exec(element.className);
+        if (match !== null) {
+            startFrom = toNumber(match[1], defaultValue);
+        }
+
         // can be overridden because local option is priority
         var value = getAttribute(element, 'data-ln-start-from');
         if (value !== null) {
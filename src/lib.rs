@@ -1,55 +1,187 @@
 #![doc = include_str!("../README.md")]
 
-use std::iter::once;
-use std::marker::PhantomData;
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::iter::{self, once};
+use std::path::{Component, Path, PathBuf};
+use std::ptr;
 
-use anyhow::anyhow;
-pub use config::{CodeConfig, HeadingConfig, NumberingConfig, NumberingStyle};
-use either::Either;
-use mdbook_preprocessor::book::{Book, BookItem};
+use anyhow::{Context as _, bail};
+pub use config::{
+    AssetsConfig, AssetsMode, ChapterErrorPolicy, CodeConfig, DiagnosticsConfig, GutterStyle,
+    HeadingConfig, HeadingRender, MarkdownExtension, NumberingConfig, NumberingStyle, RewriteMode,
+};
+pub use diagnostic::{Diagnostic, DiagnosticKind};
+pub use format::{FormattedNumber, HeadingContext, NumberFormatter, SectionNumberFormatter};
+pub use heading::NumberHeadings;
+use mdbook_preprocessor::book::{Book, BookItem, Chapter, SectionNumber};
 use mdbook_preprocessor::config::Config;
 use mdbook_preprocessor::errors::Error;
 use mdbook_preprocessor::{Preprocessor, PreprocessorContext};
-use pulldown_cmark::{CowStr, Event, Parser, Tag};
-use pulldown_cmark_to_cmark::{State, cmark_resume_with_options};
+pub use outline::{Outline, OutlineChapter, OutlineHeading};
+/// The version of `pulldown-cmark` whose events [`NumberHeadings`] takes and yields.
+///
+/// As its types are part of the API, a breaking release of `pulldown-cmark`
+/// is adopted in a breaking release of this crate.
+pub use pulldown_cmark;
+use pulldown_cmark::{CowStr, Event, Parser, Tag, TagEnd};
+use pulldown_cmark_to_cmark::cmark_resume_with_options;
+#[cfg(feature = "parallel")]
+use rayon::iter::{IntoParallelRefIterator as _, ParallelIterator as _};
 
+use crate::assets::Assets;
+use crate::code::CodeBlocks;
+use crate::config::{explain_error, merge_table};
+use crate::diagnostic::{Severity, Source};
+use crate::heading::HeadingNumberer;
+use crate::roundtrip::Inserted;
+use crate::splice::{Splice, SplicedHeading};
+
+mod assets;
+mod code;
 mod config;
+mod diagnostic;
+mod format;
+mod heading;
+mod order;
+mod outline;
+mod roundtrip;
+mod splice;
 #[cfg(test)]
 mod tests;
 
-static HIGHLIGHT_JS_LINE_NUMBERS_JS: &str = concat!(
-    "<script defer>\n\
-        window.addEventListener('DOMContentLoaded', function() { ",
-    include_str!("highlightjs/line-numbers-min.js"),
-    " });\n\
-    </script>\n",
-);
-
-static HIGHLIGHT_JS_LINE_NUMBERS_CSS: &str = concat!(
-    "<style>\n",
-    include_str!("highlightjs/line-numbers-min.css"),
-    "\n</style>\n",
-);
-
-static SECTION_NUMBERS_CSS: &str = concat!(
-    "<style>",
-    include_str!("heading/numbering-min.css"),
-    "</style>\n"
-);
-
-static SECTION_NUMBERS_PRINT_HIDE_CSS: &str = concat!(
-    "<style>",
-    include_str!("heading/hide-min.css"),
-    "</style>\n"
-);
+/// The kind of output the numbered chapters are rendered to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum OutputFormat {
+    /// HTML, with heading numbers in `<span>` elements or attributes,
+    /// and CSS and JavaScript assets.
+    Html,
+    /// Plain text, with heading numbers as part of the heading text,
+    /// and no HTML at all.
+    Text,
+}
+
+impl OutputFormat {
+    /// The output format for the mdBook renderer named `renderer`.
+    ///
+    /// Only the built-in `html` renderer gets HTML output, as other renderers
+    /// (e.g. `markdown`, `epub` or plain-text ones) cannot make use of
+    /// the injected `<span>`, `<style>` and `<script>` elements.
+    fn for_renderer(renderer: &str) -> Self {
+        match renderer {
+            "html" => Self::Html,
+            _ => Self::Text,
+        }
+    }
+}
+
+/// Number the headings and code blocks of `chapter` for the HTML renderer,
+/// and return the diagnostics found.
+///
+/// The embedded assets are appended to the chapter if [`AssetsConfig::mode`](AssetsConfig)
+/// is `inline`. In `files` mode, only the markup is added and no asset files are written,
+/// so the caller must provide the CSS and JavaScript. Asset files configured in
+/// [`NumberingConfig::assets`] are not read in either mode. Draft chapters are left unmodified.
+///
+/// # Errors
+///
+/// Returns an error naming the chapter if it cannot be numbered,
+/// in which case the chapter is left unmodified.
+pub fn number_chapter(
+    chapter: &mut Chapter,
+    config: &NumberingConfig,
+) -> Result<Vec<Diagnostic>, Error> {
+    if chapter.is_draft_chapter() {
+        return Ok(Vec::new());
+    }
+    number_html(chapter, config)
+}
+
+/// Number the headings and code blocks of the Markdown of a chapter numbered `number`
+/// for the HTML renderer, and return the numbered Markdown with the diagnostics found.
+///
+/// See [`number_chapter`] for details.
+///
+/// # Errors
+///
+/// Returns an error if the Markdown cannot be numbered.
+pub fn number_markdown(
+    markdown: &str,
+    number: &SectionNumber,
+    config: &NumberingConfig,
+) -> Result<(String, Vec<Diagnostic>), Error> {
+    let mut chapter = Chapter {
+        content: markdown.to_owned(),
+        number: Some(number.clone()),
+        ..Chapter::default()
+    };
+    let diagnostics = number_html(&mut chapter, config)?;
+    Ok((chapter.content, diagnostics))
+}
+
+/// List the chapters of `book` with their headings, as numbered for the HTML renderer.
+///
+/// Use [`NumberingPreprocessor::outline`] to number them with a custom [`NumberFormatter`].
+pub fn outline(book: &Book, config: &NumberingConfig) -> Outline {
+    NumberingPreprocessor::new().outline(book, config)
+}
+
+/// Number `chapter` for the HTML renderer with the embedded assets,
+/// and collect the diagnostics.
+fn number_html(chapter: &mut Chapter, config: &NumberingConfig) -> Result<Vec<Diagnostic>, Error> {
+    let assets = Assets::new(config);
+    let mut diagnostics = Vec::new();
+    let content = NumberingPreprocessor::new().render_chapter(
+        chapter,
+        config,
+        &assets,
+        OutputFormat::Html,
+        Path::new(""),
+        |err| diagnostics.extend(err.downcast::<Diagnostic>()),
+    )?;
+    if let Some(content) = content {
+        chapter.content = content;
+    }
+    Ok(diagnostics)
+}
 
 /// mdbook preprocessor for adding numbering to headings and code blocks.
-pub struct NumberingPreprocessor(PhantomData<()>);
+pub struct NumberingPreprocessor {
+    /// Formatter of heading numbers, or `None` for [`SectionNumberFormatter`].
+    formatter: Option<Box<dyn NumberFormatter>>,
+}
 
 impl NumberingPreprocessor {
     /// Create a new `NumberingPreprocessor`.
     pub const fn new() -> Self {
-        Self(PhantomData)
+        Self { formatter: None }
+    }
+
+    /// Create a new `NumberingPreprocessor` that formats heading numbers with `formatter`.
+    pub fn with_formatter(formatter: impl NumberFormatter + 'static) -> Self {
+        Self {
+            formatter: Some(Box::new(formatter)),
+        }
+    }
+
+    fn formatter(&self) -> &dyn NumberFormatter {
+        self.formatter.as_deref().unwrap_or(&SectionNumberFormatter)
+    }
+
+    /// List the chapters of `book` with their headings, as numbered for the HTML renderer
+    /// with the formatter of this preprocessor.
+    pub fn outline(&self, book: &Book, config: &NumberingConfig) -> Outline {
+        self.outline_with(book, config, OutputFormat::Html)
+    }
+
+    fn outline_with(&self, book: &Book, config: &NumberingConfig, format: OutputFormat) -> Outline {
+        let chapters = book.iter().filter_map(|item| match item {
+            BookItem::Chapter(ch) => Some(outline::chapter(ch, config, self.formatter(), format)),
+            _ => None,
+        });
+        Outline {
+            chapters: chapters.collect(),
+        }
     }
 }
 
@@ -60,200 +192,397 @@ impl Default for NumberingPreprocessor {
 }
 
 impl NumberingPreprocessor {
-    fn parser_options() -> pulldown_cmark::Options {
-        use pulldown_cmark::Options;
-        let mut options = Options::empty();
-        options.insert(Options::ENABLE_TABLES);
-        options.insert(Options::ENABLE_FOOTNOTES);
-        options.insert(Options::ENABLE_STRIKETHROUGH);
-        options.insert(Options::ENABLE_TASKLISTS);
-        // options.insert(Options::ENABLE_SMART_PUNCTUATION);
-        options.insert(Options::ENABLE_HEADING_ATTRIBUTES);
-        // options.insert(Options::ENABLE_YAML_STYLE_METADATA_BLOCKS);
-        // options.insert(Options::ENABLE_PLUSES_DELIMITED_METADATA_BLOCKS);
-        // options.insert(Options::ENABLE_OLD_FOOTNOTES);
-        options.insert(Options::ENABLE_MATH);
-        options.insert(Options::ENABLE_GFM);
-        options.insert(Options::ENABLE_DEFINITION_LIST);
-        options.insert(Options::ENABLE_SUPERSCRIPT);
-        options.insert(Options::ENABLE_SUBSCRIPT);
-        // options.insert(Options::ENABLE_WIKILINKS);
-        options
+    /// The options to parse chapters with, according to
+    /// [`NumberingConfig::markdown_extensions`].
+    fn parser_options(config: &NumberingConfig) -> pulldown_cmark::Options {
+        let extensions = config
+            .markdown_extensions
+            .as_deref()
+            .unwrap_or(MarkdownExtension::DEFAULT);
+        extensions
+            .iter()
+            .fold(pulldown_cmark::Options::empty(), |options, extension| {
+                options | extension.flag()
+            })
+    }
+
+    /// Whether chapter `ch` gets anything numbered for `format`,
+    /// i.e. has headings or code blocks that are numbered with `config`.
+    ///
+    /// Other chapters are left byte for byte as they are, without the assets.
+    /// This only parses the chapter, which is cheaper than numbering it.
+    fn needs_numbering(ch: &Chapter, config: &NumberingConfig, format: OutputFormat) -> bool {
+        let html = format == OutputFormat::Html;
+        // Headings of unnumbered chapters only get an attribute in HTML output.
+        let headings = config.heading.enable && (html || ch.number.is_some());
+        let code = html && config.code.enable;
+        if !headings && !code {
+            return false;
+        }
+        Parser::new_ext(&ch.content, Self::parser_options(config)).any(|event| match event {
+            Event::Start(Tag::Heading { .. }) => headings,
+            Event::Start(Tag::CodeBlock(_)) => code,
+            // Raw `<pre><code>` HTML is split into several events at blank lines.
+            Event::Html(html) => code && config.code.all_blocks && html.contains("<pre"),
+            _ => false,
+        })
+    }
+
+    #[cfg(test)]
+    fn render_book_item(item: &mut BookItem, config: &NumberingConfig, cb: impl FnMut(Error)) {
+        let assets = Assets::new(config);
+        let src = Path::new("src");
+        let preprocessor = Self::new();
+        preprocessor
+            .render_book_item_with(item, config, &assets, OutputFormat::Html, src, cb)
+            .unwrap();
+    }
+
+    /// Serialize `events` to Markdown, followed by the `appended` HTML elements.
+    fn serialize<'a>(
+        events: impl Iterator<Item = Event<'a>>,
+        appended: &[String],
+        capacity: usize,
+    ) -> Result<String, pulldown_cmark_to_cmark::Error> {
+        let options = pulldown_cmark_to_cmark::Options::default();
+        let mut buf = String::with_capacity(capacity);
+        let state = cmark_resume_with_options(events, &mut buf, None, options.clone())?;
+        let state = cmark_resume_with_options(
+            (appended.iter()).map(|html| Event::InlineHtml(CowStr::from(html.as_str()))),
+            &mut buf,
+            Some(state),
+            options,
+        )?;
+        state.finalize(&mut buf)?;
+        Ok(buf)
     }
-    fn render_book_item(item: &mut BookItem, config: &NumberingConfig, mut cb: impl FnMut(Error)) {
-        let BookItem::Chapter(ch) = item else { return };
+
+    /// Number the chapter in `item`, unless it is a draft chapter.
+    ///
+    /// See [`render_chapter`](Self::render_chapter) for details.
+    #[cfg(test)]
+    fn render_book_item_with(
+        &self,
+        item: &mut BookItem,
+        config: &NumberingConfig,
+        assets: &Assets,
+        format: OutputFormat,
+        src: &Path,
+        cb: impl FnMut(Error),
+    ) -> Result<(), Error> {
+        let BookItem::Chapter(ch) = item else {
+            return Ok(());
+        };
         if ch.is_draft_chapter() {
-            return;
+            return Ok(());
         }
+        if let Some(content) = self.render_chapter(ch, config, assets, format, src, cb)? {
+            ch.content = content;
+        }
+        Ok(())
+    }
+
+    /// Number the chapter `ch`, and return its new content,
+    /// or `None` if it gets nothing numbered and is left as it is.
+    ///
+    /// Diagnostics are reported to `cb` with their location in the source file,
+    /// relative to the book root, given that the source directory is `src`.
+    ///
+    /// If the chapter cannot be numbered, an error naming the chapter is returned.
+    fn render_chapter(
+        &self,
+        ch: &Chapter,
+        config: &NumberingConfig,
+        assets: &Assets,
+        format: OutputFormat,
+        src: &Path,
+        cb: impl FnMut(Error),
+    ) -> Result<Option<String>, Error> {
+        if !Self::needs_numbering(ch, config, format) {
+            return Ok(None);
+        }
+        let html = format == OutputFormat::Html;
         let c = &ch.content;
+        let path = ch.source_path.as_ref().or(ch.path.as_ref()).map(|path| {
+            (src.components().chain(path.components()))
+                .filter(|component| *component != Component::CurDir)
+                .collect::<PathBuf>()
+        });
+        let source = Source::new(path.as_deref(), c, ch.number.as_ref());
+        // Diagnostics of headings and code blocks are reported as their events are processed.
+        let cb = RefCell::new(cb);
+        let report = |offset, warning| (cb.borrow_mut())(source.locate(offset, warning).into());
 
-        let options = Self::parser_options();
+        let options = Self::parser_options(config);
 
-        let tokenized = Parser::new_ext(c, options);
+        // Code blocks are left as they are if they are not rendered to HTML.
+        let text_code_config = CodeConfig {
+            enable: false,
+            ..CodeConfig::new()
+        };
+        let code_config = if html {
+            &config.code
+        } else {
+            &text_code_config
+        };
+        let mut code = CodeBlocks::new(
+            Parser::new_ext(c, options).into_offset_iter(),
+            code_config,
+            &ch.name,
+        );
+        // The events before numbering, to compare the output with.
+        let mut expected = config.verify_roundtrip.then(Vec::new);
+        let tokenized = iter::from_fn(|| {
+            let next = code.next();
+            for (offset, warning) in code.warnings.drain(..) {
+                report(offset, warning);
+            }
+            next
+        })
+        .inspect(|(event, range)| {
+            if let Some(expected) = &mut expected {
+                expected.push((event.clone(), range.clone()));
+            }
+        });
 
-        let options = pulldown_cmark_to_cmark::Options::default();
+        let inline_assets = if html && config.assets.mode == AssetsMode::Inline {
+            assets.inline(config, ch.number.as_ref())
+        } else {
+            Vec::new()
+        };
 
-        let mut buf = String::with_capacity(c.len());
-
-        let mut state = State::default();
-
-        if config.heading.enable {
-            if let Some(a) = &ch.number {
-                let name = ch.name.clone();
-                let mut stack = a.clone();
-                let events = tokenized.flat_map(|mut event| match event {
-                    Event::Start(Tag::Heading {
-                        level,
-                        ref mut attrs,
-                        ..
-                    }) => {
-                        let level_depth = match config.heading.numbering_style {
-                            NumberingStyle::Consecutive => level as usize,
-                            NumberingStyle::Top => level as usize + a.len() - 1,
-                        };
-                        if level_depth > stack.len() + 1 {
-                            cb(anyhow!(
-                                "\
-                            Heading level {} found, \
-                            but only {} levels in numbering \"{}\" for chapter \"{}\".",
-                                level,
-                                stack.len(),
-                                stack,
-                                name,
-                            ));
-                        }
-                        if config.heading.numbering_style == NumberingStyle::Consecutive
-                            && level_depth < a.len()
-                        {
-                            cb(anyhow!(
-                                "\
-                            Heading level {} found, \
-                            but numbering \"{}\" for chapter \"{}\" has more levels. \
-                            Consider using `numbering-style = \"top\"` in the config, \
-                            if you want the top heading to be level 1.",
-                                level,
-                                stack,
-                                name,
-                            ));
+        let mut numberer = HeadingNumberer::new(ch.number.as_ref(), &config.heading, format);
+        numberer.chapter_name(&ch.name);
+        numberer.chapter_path(ch.source_path.as_deref().or(ch.path.as_deref()));
+        numberer.formatter(self.formatter());
+
+        let buf = match config.rewrite {
+            RewriteMode::Serialize => {
+                let events = tokenized.flat_map(|(mut event, range)| {
+                    let inserted =
+                        numberer.number_event(&mut event, |warning| report(range.start, warning));
+                    once(event).chain(inserted)
+                });
+
+                Self::serialize(events, &inline_assets, c.len())
+                    .with_context(|| format!("Failed to number chapter \"{}\"", ch.name))?
+            }
+            RewriteMode::Splice => {
+                let mut splice = Splice::default();
+                let mut heading: Option<(SplicedHeading, Option<FormattedNumber>)> = None;
+                for (event, range) in tokenized {
+                    match event {
+                        Event::Start(Tag::Heading {
+                            level,
+                            id,
+                            classes,
+                            attrs,
+                        }) if numberer.enabled() => {
+                            let spliced = SplicedHeading {
+                                content: None,
+                                has_attributes: id.is_some()
+                                    || !classes.is_empty()
+                                    || !attrs.is_empty(),
+                                range: range.clone(),
+                            };
+                            let number =
+                                numberer.number(level, |warning| report(range.start, warning));
+                            heading = Some((spliced, number));
                         }
-                        while level_depth > stack.len() {
-                            stack.push(0);
+                        Event::End(TagEnd::Heading(_)) => {
+                            let Some((heading, number)) = heading.take() else {
+                                continue;
+                            };
+                            match (format, number) {
+                                (OutputFormat::Text, None) => {}
+                                (OutputFormat::Text, Some(number)) => {
+                                    // `1. ` at the start of a line would begin a list.
+                                    let setext = heading.is_setext(c);
+                                    let label = splice::escape_markdown(&number.label, setext);
+                                    splice.prepend_to_heading(c, &heading, &format!("{label} "));
+                                }
+                                (OutputFormat::Html, None) => {
+                                    splice.add_heading_attribute(c, &heading, "data-numbering");
+                                }
+                                (OutputFormat::Html, Some(number)) => {
+                                    if numberer.renders_span() {
+                                        let span = heading::span(&number.label);
+                                        splice.prepend_to_heading(c, &heading, &span);
+                                    }
+                                    let attribute = format!("data-numbering={}", number.attribute);
+                                    splice.add_heading_attribute(c, &heading, &attribute);
+                                }
+                            }
                         }
-                        stack.truncate(level_depth);
-                        // while level_depth < stack.len() {
-                        //     stack.pop();
-                        // }
-                        if level_depth > a.len() {
-                            stack[level_depth - 1] += 1;
+                        _ => {
+                            if let Some((heading, _)) = &mut heading {
+                                let content = heading.content.get_or_insert(range.clone());
+                                content.end = range.end;
+                            }
                         }
-                        attrs.push((
-                            CowStr::from("data-numbering"),
-                            Some(CowStr::from(format!("{stack}"))),
-                        ));
-                        Either::Right(
-                            [
-                                event,
-                                Event::InlineHtml(CowStr::from(format!(
-                                    "<span class=\"heading numbering\">{stack} </span>"
-                                ))),
-                            ]
-                            .into_iter(),
-                        )
                     }
-                    _ => Either::Left(once(event)),
-                });
-                state = cmark_resume_with_options(events, &mut buf, Some(state), options.clone())
-                    .unwrap();
-                state = cmark_resume_with_options(
-                    once(Event::InlineHtml(CowStr::from(SECTION_NUMBERS_CSS))),
-                    &mut buf,
-                    Some(state),
-                    options.clone(),
-                )
-                .unwrap();
-
-                if config.heading.numbering_style == NumberingStyle::Consecutive && a.len() > 1 {
-                    state = cmark_resume_with_options(
-                        once(Event::InlineHtml(CowStr::from(
-                            SECTION_NUMBERS_PRINT_HIDE_CSS,
-                        ))),
-                        &mut buf,
-                        Some(state),
-                        options.clone(),
-                    )
-                    .unwrap();
                 }
-            } else {
-                let events = tokenized.map(|mut event| match event {
-                    Event::Start(Tag::Heading { ref mut attrs, .. }) => {
-                        attrs.push((CowStr::from("data-numbering"), None));
-                        event
+                for (range, html) in code.replaced.drain(..) {
+                    splice.replace_block(c, range, &html);
+                }
+                for (offset, token) in code.continued.drain(..) {
+                    splice.add_info_token(c, offset, &token);
+                }
+
+                let mut buf = splice.apply(c);
+                if !inline_assets.is_empty() {
+                    if !buf.is_empty() && !buf.ends_with('\n') {
+                        buf.push('\n');
                     }
-                    _ => event,
-                });
-                state = cmark_resume_with_options(events, &mut buf, Some(state), options.clone())
-                    .unwrap();
+                    buf.push('\n');
+                    buf.extend(inline_assets.iter().map(String::as_str));
+                }
+                buf
             }
-        } else {
-            state = cmark_resume_with_options(tokenized, &mut buf, Some(state), options.clone())
-                .unwrap();
         };
 
-        if config.code.enable {
-            state = cmark_resume_with_options(
-                [
-                    Event::InlineHtml(CowStr::from(HIGHLIGHT_JS_LINE_NUMBERS_JS)),
-                    Event::InlineHtml(CowStr::from(HIGHLIGHT_JS_LINE_NUMBERS_CSS)),
-                ]
-                .into_iter(),
-                &mut buf,
-                Some(state),
-                options,
-            )
-            .unwrap();
+        if let Some(expected) = expected {
+            let numbered = numberer.enabled() && ch.number.is_some();
+            let inserted = Inserted {
+                span: numbered && numberer.renders_span(),
+                labels: numberer.labels,
+            };
+            let appended = inline_assets.concat();
+            if let Some((offset, difference)) =
+                roundtrip::first_difference(expected, &buf, &appended, options, &inserted)
+            {
+                let mut message = format!(
+                    "Chapter \"{}\" is changed by `mdbook-numbering` besides the numbers: \
+                    {difference}.",
+                    ch.name,
+                );
+                if config.rewrite == RewriteMode::Serialize {
+                    message.push_str(" Consider using `rewrite = \"splice\"` in the config.");
+                }
+                let warning = Diagnostic::new(DiagnosticKind::RoundtripMismatch, message);
+                report(offset, warning);
+            }
         }
 
-        state.finalize(&mut buf).unwrap();
+        Ok(Some(buf))
+    }
 
-        // eprintln!("--- Chapter '{}' Processed ---", ch.name);
-        // eprintln!("vvv Original Below\n{c:?}\n^^^ Original Above");
-        // eprintln!("vvv Processed Below\n{buf:?}\n^^^ Processed Above");
-        // eprintln!("-------------------------------");
+    /// Get the configuration in `[preprocessor.numbering]`, with the overrides in
+    /// `[preprocessor.numbering.renderer.<renderer>]` merged into it.
+    ///
+    /// Errors are explained with the valid keys and a suggestion if possible.
+    fn get_config(config: &Config, renderer: &str) -> Result<NumberingConfig, Error> {
+        let profile = format!("preprocessor.numbering.renderer.{renderer}");
+        let merged = config
+            .get::<toml::value::Table>(&profile)
+            .and_then(|overrides| {
+                let Some(overrides) = overrides else {
+                    return config.get("preprocessor.numbering");
+                };
+                let mut table: toml::value::Table =
+                    config.get("preprocessor.numbering")?.unwrap_or_default();
+                merge_table(&mut table, overrides);
+                toml::Value::Table(table)
+                    .try_into()
+                    .map(Some)
+                    .with_context(|| format!("Failed to deserialize `{profile}`"))
+            });
+        let mut numbering: NumberingConfig = merged
+            .map(Option::unwrap_or_default)
+            .map_err(explain_error)?;
+        if numbering.markdown_extensions.is_none() {
+            numbering.markdown_extensions = Self::html_markdown_extensions(config, renderer);
+        }
+        Ok(numbering)
+    }
 
-        ch.content = buf;
+    /// The default Markdown extensions, toggled by the settings in `[output.html]`
+    /// that mdBook uses when rendering, with the defaults of mdBook for unset ones.
+    ///
+    /// Returns `None` if `[output.html]` does not apply, i.e. the renderer is not `html`
+    /// and the section does not exist.
+    fn html_markdown_extensions(config: &Config, renderer: &str) -> Option<Vec<MarkdownExtension>> {
+        if renderer != "html" && !config.contains_key("output.html") {
+            return None;
+        }
+        let mut extensions = MarkdownExtension::DEFAULT.to_vec();
+        for (key, extension, default) in MarkdownExtension::HTML_SETTINGS {
+            let key = format!("output.html.{key}");
+            let enable = config.get::<bool>(&key).ok().flatten().unwrap_or(default);
+            if !enable {
+                extensions.retain(|e| *e != extension);
+            } else if !extensions.contains(&extension) {
+                extensions.push(extension);
+            }
+        }
+        Some(extensions)
     }
 
-    fn get_config(config: &Config, mut cb: impl FnMut(&Error)) -> NumberingConfig {
-        config.get("preprocessor.numbering").map_or_else(
-            |err| {
-                cb(&err);
-                NumberingConfig::default()
-            },
-            |cfg| cfg.unwrap_or_default(),
-        )
+    /// Whether invalid configuration should be replaced with the default one
+    /// instead of failing the build, according to `ignore-config-errors`.
+    fn ignore_config_errors(config: &Config) -> bool {
+        config
+            .get("preprocessor.numbering.ignore-config-errors")
+            .ok()
+            .flatten()
+            .unwrap_or(false)
     }
 
-    fn validate_config(config: &NumberingConfig, original: &Config, cb: impl FnMut(Error)) {
-        let _ = config;
-        let _ = original;
-        // Add validation logic here if needed in the future.
-        let _ = cb;
+    /// Whether the preprocessor should run for the renderer named `renderer`,
+    /// according to the `renderers` key in `[preprocessor.numbering]` of `config`.
+    ///
+    /// Configuration errors are ignored here, and reported when the book is processed.
+    pub fn supports_renderer_in(config: &Config, renderer: &str) -> bool {
+        Self::get_config(config, renderer).map_or(true, |config| config.supports_renderer(renderer))
     }
-}
 
-impl Preprocessor for NumberingPreprocessor {
-    fn name(&self) -> &str {
-        "numbering"
+    /// Report problems in `config` that do not prevent numbering,
+    /// e.g. the order of the preprocessors.
+    fn validate_config(&self, config: &Config, cb: impl FnMut(Error)) {
+        order::check_order(config, self.name(), cb);
     }
 
-    fn run(&self, ctx: &PreprocessorContext, mut book: Book) -> Result<Book, Error> {
-        let config: NumberingConfig = Self::get_config(&ctx.config, |err| {
-            eprintln!("Using default config for mdbook-numbering due to config error: {err}")
-        });
+    /// Run the preprocessor on `book`, numbering each chapter with `render_chapter`,
+    /// which is [`render_chapter`](Self::render_chapter) unless chapters fail in tests.
+    fn run_with(
+        &self,
+        ctx: &PreprocessorContext,
+        mut book: Book,
+        render_chapter: impl Fn(
+            &Chapter,
+            &NumberingConfig,
+            &Assets,
+            OutputFormat,
+            &Path,
+            &mut dyn FnMut(Error),
+        ) -> Result<Option<String>, Error>
+        + Sync,
+    ) -> Result<Book, Error> {
+        let config = match Self::get_config(&ctx.config, &ctx.renderer) {
+            Ok(config) => config,
+            Err(err) if Self::ignore_config_errors(&ctx.config) => {
+                eprintln!("Using default config for mdbook-numbering due to config error: {err}");
+                NumberingConfig::default()
+            }
+            Err(err) => return Err(err),
+        };
 
-        Self::validate_config(&config, &ctx.config, |err| {
+        let severity = if config.deny_warnings {
+            Severity::Error
+        } else {
+            Severity::Warning
+        };
+        let mut warnings = 0usize;
+        let mut json = Vec::new();
+        let mut report = |err: Error| {
             eprintln!("mdbook-numbering: {err}");
-        });
+            if let Some(diagnostic) = err.downcast_ref::<Diagnostic>() {
+                json.push(diagnostic.to_json(severity));
+            }
+            warnings += 1;
+        };
+
+        self.validate_config(&ctx.config, &mut report);
 
         // eprintln!("mdbook-numbering: Using config: {config:#?}");
         // eprintln!("mdbook-numbering: Processing book...");
@@ -261,9 +590,99 @@ impl Preprocessor for NumberingPreprocessor {
         // eprintln!("Book before processing:\n{book:#?}");
         // eprintln!("-----------------------------------");
 
-        book.for_each_mut(|item| {
-            Self::render_book_item(item, &config, |err| eprintln!("mdbook-numbering: {err}"));
+        // mdBook already skips this preprocessor for renderers not in `renderers`,
+        // so this only matters when it is run by other tools.
+        if !config.supports_renderer(&ctx.renderer) {
+            return Ok(book);
+        }
+
+        let assets = Assets::load(&config, &ctx.root)?;
+        let format = OutputFormat::for_renderer(&ctx.renderer);
+
+        if format == OutputFormat::Html && config.assets.mode == AssetsMode::Files {
+            let paths = assets.write(&config, &ctx.root)?;
+            assets::check_references(&ctx.config, &paths, &mut report);
+        }
+
+        // The outline is taken before the chapters are rewritten.
+        let outline =
+            (config.outline_json.as_ref()).map(|_| self.outline_with(&book, &config, format));
+
+        // Chapters are numbered independently, in parallel with the `parallel` feature,
+        // and then updated in the order of the book, along with their diagnostics.
+        let src = &ctx.config.book.src;
+        let render = |ch: &Chapter| {
+            let mut diagnostics = Vec::new();
+            let content = render_chapter(ch, &config, &assets, format, src, &mut |err| {
+                diagnostics.push(err)
+            });
+            (content, diagnostics)
+        };
+        let chapters: Vec<&Chapter> = book.chapters().collect();
+        #[cfg(feature = "parallel")]
+        let rendered: Vec<_> = chapters.par_iter().map(|ch| render(ch)).collect();
+        #[cfg(not(feature = "parallel"))]
+        let rendered: Vec<_> = chapters.iter().map(|ch| render(ch)).collect();
+
+        // New contents are keyed by the address of their chapter, which stays the same
+        // as long as the book is not restructured, as mdBook traverses chapters
+        // in a different order when mutating them.
+        let mut contents = HashMap::new();
+        let mut result = Ok(());
+        for (ch, (content, diagnostics)) in chapters.into_iter().zip(rendered) {
+            diagnostics.into_iter().for_each(&mut report);
+            match (content, config.on_chapter_error) {
+                (Ok(Some(content)), _) => {
+                    contents.insert(ptr::from_ref(ch), content);
+                }
+                (Ok(None), _) => {}
+                (Err(err), ChapterErrorPolicy::Fail) => {
+                    result = Err(err);
+                    break;
+                }
+                (Err(err), ChapterErrorPolicy::Skip) => {
+                    let message = format!("{err:#}. The chapter is left unmodified.");
+                    let mut warning = Diagnostic::new(DiagnosticKind::ChapterSkipped, message);
+                    warning.chapter = ch.number.as_ref().map(ToString::to_string);
+                    report(warning.into());
+                }
+            }
+        }
+        book.for_each_chapter_mut(|ch| {
+            if let Some(content) = contents.remove(&ptr::from_ref(&*ch)) {
+                ch.content = content;
+            }
         });
+
+        // The outline and diagnostics are written even if a chapter failed,
+        // but the error of the chapter takes precedence.
+        if let (Some(path), Some(outline)) = (&config.outline_json, outline) {
+            result = result.and(outline.write(path, &ctx.root).map_err(Error::from));
+        }
+        if let Some(path) = &config.diagnostics.json {
+            let written = diagnostic::write_json(path, &ctx.root, &json);
+            result = result.and(written.map_err(Error::from));
+        }
+        result?;
+
+        if config.deny_warnings && warnings > 0 {
+            bail!(
+                "mdbook-numbering found {warnings} warning(s), \
+                which are denied by `deny-warnings = true` in `[preprocessor.numbering]`."
+            );
+        }
         Ok(book)
     }
 }
+
+impl Preprocessor for NumberingPreprocessor {
+    fn name(&self) -> &str {
+        "numbering"
+    }
+
+    fn run(&self, ctx: &PreprocessorContext, book: Book) -> Result<Book, Error> {
+        self.run_with(ctx, book, |ch, config, assets, format, src, cb| {
+            self.render_chapter(ch, config, assets, format, src, cb)
+        })
+    }
+}
@@ -1,8 +1,10 @@
 #![doc = include_str!("../README.md")]
 
 use std::io;
+use std::path::Path;
 
 use mdbook_numbering::NumberingPreprocessor;
+use mdbook_preprocessor::config::Config;
 use mdbook_preprocessor::errors::Error;
 use mdbook_preprocessor::{Preprocessor, parse_input};
 
@@ -10,7 +12,31 @@ fn main() {
     let mut args = std::env::args().skip(1);
     match args.next().as_deref() {
         Some("supports") => {
-            // Supports all renderers.
+            let Some(renderer) = args.next() else {
+                eprintln!("missing renderer name for `supports`");
+                std::process::exit(1);
+            };
+            // mdBook runs this command in the book root, and uses the default config
+            // if there is no `book.toml`. Errors reading it are reported,
+            // and the default config is used as well.
+            let path = Path::new("book.toml");
+            let mut config = if path.exists() {
+                Config::from_disk(path).unwrap_or_else(|err| {
+                    eprintln!("mdbook-numbering: Failed to read `book.toml`: {err:#}");
+                    Config::default()
+                })
+            } else {
+                Config::default()
+            };
+            if let Err(err) = config.update_from_env() {
+                eprintln!(
+                    "mdbook-numbering: Failed to update the config from the environment: {err:#}"
+                );
+            }
+            if !NumberingPreprocessor::supports_renderer_in(&config, &renderer) {
+                eprintln!("mdbook-numbering: renderer `{renderer}` is not supported");
+                std::process::exit(1);
+            }
             return;
         }
         Some(arg) => {
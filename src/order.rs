@@ -0,0 +1,117 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use mdbook_preprocessor::config::Config;
+use mdbook_preprocessor::errors::Error;
+use serde::Deserialize;
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind};
+
+/// Preprocessors whose output `mdbook-numbering` depends on,
+/// so `mdbook-numbering` should run after them.
+///
+/// - `links` expands `{{#include}}` and similar directives, which may contain headings and code
+///   blocks.
+/// - `admonish` and `toc` replace code blocks and markers with generated Markdown.
+/// - `katex` renders math in the original Markdown, which may be escaped when
+///   `mdbook-numbering` serializes the Markdown again.
+const RUN_AFTER: [&str; 4] = ["admonish", "katex", "links", "toc"];
+
+/// Preprocessors that are built into mdBook.
+const DEFAULT_PREPROCESSORS: [&str; 2] = ["index", "links"];
+
+/// The fields of a `[preprocessor.*]` table that matter for ordering.
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PreprocessorTable {
+    before: Vec<String>,
+    after: Vec<String>,
+    command: Option<String>,
+}
+
+impl PreprocessorTable {
+    /// Whether this table configures the preprocessor `kind`,
+    /// either by its name or by its command.
+    fn is(&self, name: &str, kind: &str) -> bool {
+        name == kind
+            || self
+                .command
+                .as_deref()
+                .is_some_and(|command| command.contains(&format!("mdbook-{kind}")))
+    }
+}
+
+/// The order in which mdBook runs the preprocessors configured in `config`.
+///
+/// Like mdBook, preprocessors are sorted topologically by their `before` and `after` fields,
+/// and alphabetically among those that are ready to run at the same time.
+/// Preprocessors that are part of a cycle are left out.
+fn run_order(tables: &BTreeMap<String, PreprocessorTable>) -> Vec<&str> {
+    let mut dependencies: BTreeMap<&str, BTreeSet<&str>> = tables
+        .keys()
+        .map(|name| (name.as_str(), BTreeSet::new()))
+        .collect();
+    for (name, table) in tables {
+        for before in &table.before {
+            if let Some(deps) = dependencies.get_mut(before.as_str()) {
+                deps.insert(name);
+            }
+        }
+        for after in &table.after {
+            if tables.contains_key(after) {
+                dependencies.get_mut(name.as_str()).unwrap().insert(after);
+            }
+        }
+    }
+
+    let mut order = Vec::with_capacity(dependencies.len());
+    loop {
+        let ready: Vec<&str> = dependencies
+            .iter()
+            .filter(|(_, deps)| deps.is_empty())
+            .map(|(name, _)| *name)
+            .collect();
+        if ready.is_empty() {
+            break;
+        }
+        for name in &ready {
+            dependencies.remove(name);
+        }
+        for deps in dependencies.values_mut() {
+            for name in &ready {
+                deps.remove(name);
+            }
+        }
+        order.extend(ready);
+    }
+    order
+}
+
+/// Check that the preprocessor named `name` runs after the preprocessors in [`RUN_AFTER`],
+/// and report each one that runs later.
+pub(crate) fn check_order(config: &Config, name: &str, mut cb: impl FnMut(Error)) {
+    let Ok(mut tables) = config.preprocessors::<PreprocessorTable>() else {
+        return;
+    };
+    if config.build.use_default_preprocessors {
+        for default in DEFAULT_PREPROCESSORS {
+            tables.entry(default.to_owned()).or_default();
+        }
+    }
+
+    let order = run_order(&tables);
+    let Some(position) = order.iter().position(|other| *other == name) else {
+        return;
+    };
+    for other in &order[position + 1..] {
+        let table = &tables[*other];
+        if let Some(kind) = RUN_AFTER.iter().find(|kind| table.is(other, kind)) {
+            let message = format!(
+                "\
+                Preprocessor `{name}` runs before `{other}`, \
+                but it should run after `{kind}` preprocessors to number their output. \
+                Consider adding `after = [\"{other}\"]` to `[preprocessor.{name}]`.",
+            );
+            cb(Diagnostic::new(DiagnosticKind::PreprocessorOrder, message).into());
+        }
+    }
+}
@@ -0,0 +1,171 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::{fs, io};
+
+use mdbook_preprocessor::book::{Chapter, SectionNumber};
+use pulldown_cmark::{Event, Parser, Tag, TagEnd};
+use serde::Serialize;
+
+use crate::heading::HeadingNumberer;
+use crate::{NumberFormatter, NumberingConfig, NumberingPreprocessor, OutputFormat};
+
+/// The chapters of a book with their headings and numbers,
+/// as returned by [`outline`](crate::outline) and written to
+/// [`NumberingConfig::outline_json`].
+#[derive(Debug, Clone, Default, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+pub struct Outline {
+    /// The chapters, in the order of the book, including draft chapters.
+    pub chapters: Vec<OutlineChapter>,
+}
+
+/// A chapter in an [`Outline`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+pub struct OutlineChapter {
+    /// The name of the chapter.
+    pub name: String,
+    /// The number of the chapter, or `None` if it is not numbered.
+    pub number: Option<SectionNumber>,
+    /// The path of the chapter, relative to the source directory,
+    /// or `None` for a draft chapter.
+    pub path: Option<PathBuf>,
+    /// The path of the source file of the chapter, relative to the source directory.
+    pub source_path: Option<PathBuf>,
+    /// The headings of the chapter, in order.
+    pub headings: Vec<OutlineHeading>,
+}
+
+/// A heading in an [`OutlineChapter`].
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[non_exhaustive]
+#[serde(rename_all = "kebab-case")]
+pub struct OutlineHeading {
+    /// The counters of the heading, e.g. `1.2.3.`,
+    /// or `None` if heading numbering is disabled or the chapter is not numbered.
+    pub number: Option<SectionNumber>,
+    /// The label of the heading as rendered by the [`NumberFormatter`],
+    /// or `None` if the heading is not numbered.
+    pub label: Option<String>,
+    /// The level of the heading, from 1 for `#` to 6 for `######`.
+    pub level: usize,
+    /// The text of the heading, without the number.
+    pub text: String,
+    /// The id of the heading, which mdBook uses as its anchor,
+    /// either set in the Markdown, e.g. `{#id}`, or derived from the rendered text.
+    pub id: String,
+}
+
+impl Outline {
+    /// Write the outline to `path` relative to `root` as pretty-printed JSON.
+    pub(crate) fn write(&self, path: &Path, root: &Path) -> io::Result<()> {
+        let mut json = serde_json::to_string_pretty(self)?;
+        json.push('\n');
+        fs::write(root.join(path), json)
+    }
+}
+
+/// The outline of chapter `ch`, numbered with `config` and `formatter` for `format`.
+pub(crate) fn chapter(
+    ch: &Chapter,
+    config: &NumberingConfig,
+    formatter: &dyn NumberFormatter,
+    format: OutputFormat,
+) -> OutlineChapter {
+    let mut numberer = HeadingNumberer::new(ch.number.as_ref(), &config.heading, format);
+    numberer.chapter_name(&ch.name);
+    numberer.chapter_path(ch.source_path.as_deref().or(ch.path.as_deref()));
+    numberer.formatter(formatter);
+
+    let mut headings = Vec::new();
+    // The heading being read, with its explicit id if any.
+    let mut heading: Option<(OutlineHeading, Option<String>)> = None;
+    let mut ids = HashSet::new();
+    let options = NumberingPreprocessor::parser_options(config);
+    for event in Parser::new_ext(&ch.content, options) {
+        match event {
+            Event::Start(Tag::Heading { level, id, .. }) => {
+                let numbered = if numberer.enabled() {
+                    numberer.number_with_counters(level, |_| {})
+                } else {
+                    None
+                };
+                let (number, label) = numbered.map(|(n, f)| (n, f.label)).unzip();
+                let outlined = OutlineHeading {
+                    number,
+                    label,
+                    level: level as usize,
+                    text: String::new(),
+                    id: String::new(),
+                };
+                heading = Some((outlined, id.map(|id| id.to_string())));
+            }
+            Event::End(TagEnd::Heading(_)) => {
+                let Some((mut outlined, id)) = heading.take() else {
+                    continue;
+                };
+                outlined.id = id.unwrap_or_else(|| {
+                    // The label is part of the heading text unless it is only an attribute.
+                    let label = (outlined.label.as_ref())
+                        .filter(|_| format == OutputFormat::Text || numberer.renders_span());
+                    let text = match label {
+                        Some(label) => format!("{label} {}", outlined.text),
+                        None => outlined.text.clone(),
+                    };
+                    unique_id(id_from_text(&text), &mut ids)
+                });
+                ids.insert(outlined.id.clone());
+                headings.push(outlined);
+            }
+            Event::Text(text) | Event::Code(text) => {
+                if let Some((outlined, _)) = &mut heading {
+                    outlined.text.push_str(&text);
+                }
+            }
+            Event::SoftBreak | Event::HardBreak => {
+                if let Some((outlined, _)) = &mut heading {
+                    outlined.text.push(' ');
+                }
+            }
+            _ => {}
+        }
+    }
+
+    OutlineChapter {
+        name: ch.name.clone(),
+        number: ch.number.clone(),
+        path: ch.path.clone(),
+        source_path: ch.source_path.clone(),
+        headings,
+    }
+}
+
+/// The id that mdBook derives from the text of a heading:
+/// alphanumeric characters, `_` and `-` in lowercase, with whitespace replaced by `-`.
+fn id_from_text(text: &str) -> String {
+    text.trim()
+        .chars()
+        .filter_map(|c| {
+            if c.is_alphanumeric() || c == '_' || c == '-' {
+                Some(c.to_ascii_lowercase())
+            } else if c.is_whitespace() {
+                Some('-')
+            } else {
+                None
+            }
+        })
+        .collect()
+}
+
+/// Make `id` unique among the `used` ids of a chapter by appending `-1`, `-2`, etc.
+fn unique_id(id: String, used: &mut HashSet<String>) -> String {
+    if !used.contains(&id) {
+        return id;
+    }
+    (1..)
+        .map(|i| format!("{id}-{i}"))
+        .find(|candidate| !used.contains(candidate))
+        .unwrap()
+}
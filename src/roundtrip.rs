@@ -0,0 +1,132 @@
+use std::ops::Range;
+
+use pulldown_cmark::{CowStr, Event, Parser, Tag};
+
+/// Start of the `<span>` element inserted before the content of numbered headings.
+const SPAN_START: &str = "<span class=\"heading numbering\">";
+
+/// The events that `mdbook-numbering` inserts into headings,
+/// which are removed from the output before it is compared with the input.
+#[derive(Debug, Clone)]
+pub(crate) struct Inserted {
+    /// A `<span>` element holding the number.
+    pub(crate) span: bool,
+    /// The labels inserted as plain text, each followed by a space,
+    /// in the order of the headings.
+    pub(crate) labels: Vec<String>,
+}
+
+/// Merge adjacent text and adjacent HTML events, which may be split differently
+/// depending on how the Markdown is written, keeping the position of the first one.
+fn normalize<'a, P>(events: impl IntoIterator<Item = (Event<'a>, P)>) -> Vec<(Event<'a>, P)> {
+    let mut normalized: Vec<(Event<'a>, P)> = Vec::new();
+    for (event, position) in events {
+        match (normalized.last_mut(), &event) {
+            (Some((Event::Text(last), _)), Event::Text(text))
+            | (Some((Event::Html(last), _)), Event::Html(text)) => {
+                *last = CowStr::from(format!("{last}{text}"));
+            }
+            _ => normalized.push((event, position)),
+        }
+    }
+    normalized
+}
+
+/// Parse `output` and remove the events in `inserted`, as well as `data-numbering` attributes.
+fn parse_output<'a>(
+    output: &'a str,
+    options: pulldown_cmark::Options,
+    inserted: &Inserted,
+) -> Vec<Event<'a>> {
+    let mut events = Vec::new();
+    let mut parser = Parser::new_ext(output, options).peekable();
+    while let Some(mut event) = parser.next() {
+        let Event::Start(Tag::Heading { attrs, .. }) = &mut event else {
+            events.push(event);
+            continue;
+        };
+        attrs.retain(|(key, _)| key.as_ref() != "data-numbering");
+        events.push(event);
+        if inserted.span
+            && parser
+                .next_if(|event| *event == Event::InlineHtml(SPAN_START.into()))
+                .is_some()
+        {
+            while parser
+                .next_if(|event| matches!(event, Event::Text(_)))
+                .is_some()
+            {}
+            parser.next_if(|event| *event == Event::InlineHtml("</span>".into()));
+        }
+    }
+
+    let mut events: Vec<_> = normalize(events.into_iter().map(|event| (event, ())))
+        .into_iter()
+        .map(|(event, ())| event)
+        .collect();
+    let mut labels = inserted.labels.iter();
+    let mut i = 0;
+    while i + 1 < events.len() {
+        if let Event::Start(Tag::Heading { .. }) = &events[i]
+            && let Some(label) = labels.next()
+            && let Event::Text(text) = &events[i + 1]
+        {
+            // The trailing space is trimmed if the heading is otherwise empty.
+            match text.strip_prefix(label.as_str()) {
+                Some("" | " ") => {
+                    events.remove(i + 1);
+                }
+                Some(rest) if rest.starts_with(' ') => {
+                    events[i + 1] = Event::Text(CowStr::from(rest[1..].to_owned()));
+                }
+                _ => {}
+            }
+        }
+        i += 1;
+    }
+    events
+}
+
+/// Compare the events of the chapter before numbering, paired with their source ranges,
+/// with the events parsed from `output`, once the `inserted` events are removed.
+///
+/// The HTML blocks of the `appended` assets are removed from the end of `output`,
+/// unless they are merged into the last block of the chapter.
+///
+/// Returns the source offset of the first differing event,
+/// along with a description of the expected and found events.
+pub(crate) fn first_difference(
+    expected: Vec<(Event<'_>, Range<usize>)>,
+    output: &str,
+    appended: &str,
+    options: pulldown_cmark::Options,
+    inserted: &Inserted,
+) -> Option<(usize, String)> {
+    let expected = normalize(expected);
+    let mut found = parse_output(output, options, inserted);
+    let appended = parse_output(appended, options, inserted);
+    if found.ends_with(&appended) {
+        found.truncate(found.len() - appended.len());
+    }
+
+    let index = (expected.iter().zip(&found))
+        .position(|((expected, _), found)| expected != found)
+        .unwrap_or(expected.len().min(found.len()));
+    let describe = |event: Option<&Event>| {
+        event.map_or_else(
+            || "end of chapter".to_owned(),
+            |event| format!("`{event:?}`"),
+        )
+    };
+    let offset = match (expected.get(index), found.get(index)) {
+        (None, None) => return None,
+        (Some((_, range)), _) => range.start,
+        (None, Some(_)) => expected.last().map_or(0, |(_, range)| range.end),
+    };
+    let description = format!(
+        "expected {}, found {}",
+        describe(expected.get(index).map(|(event, _)| event)),
+        describe(found.get(index)),
+    );
+    Some((offset, description))
+}
@@ -0,0 +1,173 @@
+use std::ops::Range;
+
+/// A heading in the Markdown source of a chapter.
+#[derive(Debug, Clone)]
+pub(crate) struct SplicedHeading {
+    /// Source range of the heading.
+    pub(crate) range: Range<usize>,
+    /// Source range of the inline content of the heading, or `None` if it is empty.
+    pub(crate) content: Option<Range<usize>>,
+    /// Whether the heading has an attribute block, e.g. `{#id}`.
+    pub(crate) has_attributes: bool,
+}
+
+impl SplicedHeading {
+    /// Whether this is a setext heading, i.e. underlined with `=` or `-`.
+    pub(crate) fn is_setext(&self, source: &str) -> bool {
+        !source[self.range.clone()].starts_with('#')
+    }
+
+    /// The end of the line holding the end of the content,
+    /// where the attribute block of the heading is, without trailing whitespace.
+    fn line_end(&self, source: &str) -> usize {
+        let end = self
+            .content
+            .as_ref()
+            .map_or(self.range.start, |content| content.end);
+        let line_end = source[end..].find('\n').map_or(source.len(), |i| end + i);
+        source[..line_end]
+            .trim_end_matches([' ', '\t', '\r'])
+            .len()
+            .max(end)
+    }
+}
+
+/// Edits to the Markdown source of a chapter, which leave every other byte untouched.
+#[derive(Debug, Default)]
+pub(crate) struct Splice {
+    /// Source ranges with their replacements, in the order they are added.
+    edits: Vec<(Range<usize>, String)>,
+}
+
+impl Splice {
+    fn insert(&mut self, offset: usize, text: String) {
+        self.edits.push((offset..offset, text));
+    }
+
+    /// Insert `text` at the beginning of the content of `heading`,
+    /// i.e. after the opening sequence of an ATX heading,
+    /// or at the start of the first line of a setext heading.
+    ///
+    /// The offset of the first inline event is not used, as it may be inside
+    /// the syntax of that event, e.g. after the backslash of an escape.
+    pub(crate) fn prepend_to_heading(
+        &mut self,
+        source: &str,
+        heading: &SplicedHeading,
+        text: &str,
+    ) {
+        let start = heading.range.start;
+        let rest = &source[start..];
+        if heading.is_setext(source) {
+            let indent = rest.len() - rest.trim_start_matches([' ', '\t']).len();
+            self.insert(start + indent, text.to_owned());
+            return;
+        }
+        let opening = start + (rest.len() - rest.trim_start_matches('#').len());
+        let after = &source[opening..];
+        let spaces = after.len() - after.trim_start_matches([' ', '\t']).len();
+        if spaces == 0 {
+            // An empty heading, e.g. `##` at the end of a line.
+            self.insert(opening, format!(" {text}"));
+        } else {
+            self.insert(opening + spaces, text.to_owned());
+        }
+    }
+
+    /// Add `attribute`, e.g. `data-numbering=1.2.`, to `heading`,
+    /// either in its attribute block or in a new one.
+    pub(crate) fn add_heading_attribute(
+        &mut self,
+        source: &str,
+        heading: &SplicedHeading,
+        attribute: &str,
+    ) {
+        let line_end = heading.line_end(source);
+        if heading.has_attributes && source[..line_end].ends_with('}') {
+            self.insert(line_end - 1, format!(" {attribute}"));
+        } else {
+            self.insert(line_end, format!(" {{ {attribute} }}"));
+        }
+    }
+
+    /// Replace the block at `range` with `text`.
+    ///
+    /// The container prefixes of the first line, e.g. `> ` or the indentation of a list item,
+    /// are repeated on the other lines of `text`.
+    pub(crate) fn replace_block(&mut self, source: &str, range: Range<usize>, text: &str) {
+        let line_start = source[..range.start].rfind('\n').map_or(0, |i| i + 1);
+        let prefix: String = source[line_start..range.start]
+            .chars()
+            .map(|c| {
+                if c == '>' || c.is_whitespace() {
+                    c
+                } else {
+                    ' '
+                }
+            })
+            .collect();
+
+        let mut buf = String::with_capacity(text.len());
+        for (i, line) in text.split_inclusive('\n').enumerate() {
+            if i > 0 {
+                buf.push_str(&prefix);
+            }
+            buf.push_str(line);
+        }
+        // The range of a fenced code block does not include the final line break.
+        if !source[range.clone()].ends_with('\n') && buf.ends_with('\n') {
+            buf.pop();
+        }
+        self.edits.push((range, buf));
+    }
+
+    /// Add `token` to the info string of the fenced code block starting at `offset`,
+    /// right after its first token, i.e. the language.
+    pub(crate) fn add_info_token(&mut self, source: &str, offset: usize, token: &str) {
+        let line = &source[offset
+            ..source[offset..]
+                .find('\n')
+                .map_or(source.len(), |i| offset + i)];
+        let fence = line.trim_start_matches([' ', '\t']);
+        let info = fence
+            .trim_start_matches(['`', '~'])
+            .trim_start_matches([' ', '\t']);
+        let language_end = info.find([' ', '\t', ',', '\r']).unwrap_or(info.len());
+        self.insert(
+            offset + (line.len() - info.len()) + language_end,
+            format!(",{token}"),
+        );
+    }
+
+    /// Apply the edits to `source`.
+    ///
+    /// Insertions at the same offset are applied in the order they are added.
+    pub(crate) fn apply(mut self, source: &str) -> String {
+        self.edits.sort_by_key(|(range, _)| range.start);
+        let extra = self.edits.iter().map(|(_, text)| text.len()).sum::<usize>();
+        let mut buf = String::with_capacity(source.len() + extra);
+        let mut last = 0;
+        for (range, text) in self.edits {
+            buf.push_str(&source[last..range.start]);
+            buf.push_str(&text);
+            last = range.end;
+        }
+        buf.push_str(&source[last..]);
+        buf
+    }
+}
+
+/// Escape ASCII punctuation in `text`, so that it is inserted into a heading as plain text.
+///
+/// Dots are only escaped at the start of a line (`line_start`),
+/// where e.g. `1. ` would begin a list.
+pub(crate) fn escape_markdown(text: &str, line_start: bool) -> String {
+    let mut buf = String::with_capacity(text.len());
+    for c in text.chars() {
+        if c.is_ascii_punctuation() && (c != '.' || line_start) {
+            buf.push('\\');
+        }
+        buf.push(c);
+    }
+    buf
+}
@@ -1,12 +1,25 @@
-use mdbook_preprocessor::book::{BookItem, Chapter, SectionNumber};
+use std::path::Path;
+use std::str::FromStr as _;
+use std::{env, fs};
+
+use anyhow::bail;
+use mdbook_preprocessor::PreprocessorContext;
+use mdbook_preprocessor::book::{Book, BookItem, Chapter, SectionNumber};
+use mdbook_preprocessor::config::Config;
+use mdbook_preprocessor::errors::Error;
 use prettydiff::basic::DiffOp;
 use prettydiff::diff_lines;
 use prettydiff::owo_colors::OwoColorize;
+use pulldown_cmark::{Event, Options, Parser, Tag, TagEnd};
 
-use crate::{CodeConfig, HeadingConfig, NumberingConfig, NumberingPreprocessor, NumberingStyle};
+use crate::assets::Assets;
+use crate::{
+    AssetsConfig, AssetsMode, CodeConfig, HeadingConfig, HeadingRender, MarkdownExtension,
+    NumberingConfig, NumberingPreprocessor, NumberingStyle, OutputFormat, RewriteMode,
+};
 
 #[track_caller]
-fn panic_on_error(err: mdbook_preprocessor::errors::Error) {
+fn panic_on_error(err: Error) {
     panic!("{err}");
 }
 
@@ -81,10 +94,14 @@ fn empty() {
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -116,7 +133,10 @@ fn complex_title() {
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             ..Default::default()
         },
         panic_on_error,
@@ -147,10 +167,14 @@ fn disabled() {
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: false,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -160,6 +184,91 @@ fn disabled() {
     assert_book_item_eq(&item, &BookItem::Chapter(chapter));
 }
 
+#[test]
+fn unchanged() {
+    let prose = "Some *text*  with\\\nescapes &amp; a list:\n\n* item\n\n<div>raw</div>\n";
+    let with_heading = format!("# Title\n\n{prose}");
+    let with_pre = format!("{prose}\n<pre><code>raw</code></pre>\n");
+    let all_blocks = NumberingConfig {
+        code: CodeConfig {
+            all_blocks: true,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    for (content, number, config, format, changed) in [
+        (
+            prose,
+            Some(vec![1]),
+            NumberingConfig::default(),
+            OutputFormat::Html,
+            false,
+        ),
+        (
+            prose,
+            Some(vec![1]),
+            all_blocks.clone(),
+            OutputFormat::Html,
+            false,
+        ),
+        (
+            &with_heading,
+            None,
+            NumberingConfig::default(),
+            OutputFormat::Text,
+            false,
+        ),
+        (
+            &with_heading,
+            None,
+            NumberingConfig::default(),
+            OutputFormat::Html,
+            true,
+        ),
+        (
+            &with_pre,
+            Some(vec![1]),
+            NumberingConfig::default(),
+            OutputFormat::Html,
+            false,
+        ),
+        (
+            &with_pre,
+            Some(vec![1]),
+            all_blocks.clone(),
+            OutputFormat::Html,
+            true,
+        ),
+    ] {
+        let mut item = BookItem::Chapter(Chapter {
+            name: "Chapter 1".to_string(),
+            content: content.to_string(),
+            number: number.map(SectionNumber::new),
+            path: Some("chapter_1.md".into()),
+            ..Default::default()
+        });
+        NumberingPreprocessor::new()
+            .render_book_item_with(
+                &mut item,
+                &config,
+                &Assets::new(&config),
+                format,
+                Path::new("src"),
+                panic_on_error,
+            )
+            .unwrap();
+
+        let BookItem::Chapter(chapter) = &item else {
+            unreachable!()
+        };
+        assert_eq!(
+            chapter.content != content,
+            changed,
+            "{content:?} with {format:?}"
+        );
+    }
+}
+
 #[test]
 fn draft() {
     let chapter = Chapter {
@@ -173,10 +282,14 @@ fn draft() {
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -212,10 +325,14 @@ Some content."
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -266,10 +383,14 @@ More content.
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -325,10 +446,14 @@ More content.
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -398,10 +523,14 @@ More content.
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Top,
+                ..Default::default()
             },
             ..Default::default()
         },
@@ -437,7 +566,8 @@ More content.
 #[test]
 #[should_panic = "\
     Heading level h3 found, \
-    but only 1 levels in numbering \"1.\" for chapter \"Chapter 1\".\
+    but only 1 levels in numbering \"1.\" for chapter \"Chapter 1\".\n  \
+    --> src/chapter_1.md:3:1\
 "]
 fn inconsecutive() {
     let chapter = Chapter {
@@ -458,13 +588,767 @@ Some content."
     NumberingPreprocessor::render_book_item(
         &mut item,
         &NumberingConfig {
-            code: CodeConfig { enable: false },
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        panic_on_error,
+    );
+}
+
+#[test]
+fn ln_continue() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+```rust
+fn main() {
+    let x = 1;
+```
+
+Some prose.
+
+```rust,ln-continue
+    println!(\"{x}\");
+}
+```
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            heading: HeadingConfig {
+                enable: false,
+                numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..Default::default()
         },
         panic_on_error,
     );
+
+    let BookItem::Chapter(chapter) = item else {
+        unreachable!()
+    };
+    assert_string_eq(
+        chapter.content.split("<script").next().unwrap(),
+        "
+````rust
+fn main() {
+    let x = 1;
+````
+
+Some prose.
+
+````rust,ln-start-from-3,ln-continue
+    println!(\"{x}\");
+}
+````
+
+",
+    );
+}
+
+#[test]
+fn ln_continue_named() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+```c ln-id=first
+int a;
+int b;
+```
+
+```c
+int c;
+```
+
+```c ln-continue=first
+int d;
+```
+
+```c ln-continue=second
+int e;
+```
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    let mut warnings = Vec::new();
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            heading: HeadingConfig {
+                enable: false,
+                numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        |err| warnings.push(err.to_string()),
+    );
+
+    assert_eq!(
+        warnings,
+        ["Code block `second` referenced by `ln-continue=second` \
+            is not found in chapter \"Chapter 1\". \
+            Make sure a previous code block is marked with `ln-id=second`.\n  \
+            --> src/chapter_1.md:14:1"],
+    );
+
+    let BookItem::Chapter(chapter) = item else {
+        unreachable!()
+    };
+    assert_string_eq(
+        chapter.content.split("<script").next().unwrap(),
+        "
+````c ln-id=first
+int a;
+int b;
+````
+
+````c
+int c;
+````
+
+````c,ln-start-from-3 ln-continue=first
+int d;
+````
+
+````c,ln-start-from-1 ln-continue=second
+int e;
+````
+
+",
+    );
+}
+
+#[test]
+fn diagnostics_in_source_order() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "#### Deep\n\n```c ln-continue=missing\nint a;\n```\n\n###### Deeper\n"
+            .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+
+    for rewrite in [RewriteMode::Serialize, RewriteMode::Splice] {
+        let mut item = BookItem::Chapter(chapter.clone());
+        let mut locations = Vec::new();
+        let config = NumberingConfig::new().rewrite(rewrite);
+        NumberingPreprocessor::render_book_item(&mut item, &config, |err| {
+            locations.push(err.to_string().rsplit("--> ").next().unwrap().to_owned());
+        });
+        assert_eq!(
+            locations,
+            [
+                "src/chapter_1.md:1:1",
+                "src/chapter_1.md:3:1",
+                "src/chapter_1.md:7:1",
+            ],
+        );
+    }
+}
+
+#[test]
+fn all_blocks() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+Indented:
+
+    int a;
+    int b;
+
+```c,ln-continue
+int c;
+```
+
+<pre><code>int d;
+</code></pre>
+
+<pre class=\"x\">
+<code class=\"language-c\">int e;
+</code></pre>
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+
+    let config = NumberingConfig {
+        code: CodeConfig {
+            enable: true,
+            all_blocks: true,
+            ..Default::default()
+        },
+        heading: HeadingConfig {
+            enable: false,
+            numbering_style: NumberingStyle::Consecutive,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+
+    let mut item = BookItem::Chapter(chapter.clone());
+    NumberingPreprocessor::render_book_item(&mut item, &config, panic_on_error);
+
+    let BookItem::Chapter(numbered) = item else {
+        unreachable!()
+    };
+    assert_string_eq(
+        numbered.content.split("<script").next().unwrap(),
+        "\
+Indented:
+
+````
+int a;
+int b;
+````
+
+````c,ln-start-from-3,ln-continue
+int c;
+````
+
+<pre><code class=\"hljs\">int d;
+</code></pre>
+
+<pre class=\"x\">
+<code class=\"hljs language-c\">int e;
+</code></pre>
+
+",
+    );
+
+    let mut item = BookItem::Chapter(chapter);
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            code: CodeConfig::default(),
+            ..config
+        },
+        panic_on_error,
+    );
+
+    let BookItem::Chapter(numbered) = item else {
+        unreachable!()
+    };
+    assert_string_eq(
+        numbered.content.split("<script").next().unwrap(),
+        "\
+Indented:
+
+    int a;
+    int b;
+    \n
+````c,ln-start-from-1,ln-continue
+int c;
+````
+
+<pre><code>int d;
+</code></pre>
+
+<pre class=\"x\">
+<code class=\"language-c\">int e;
+</code></pre>
+
+",
+    );
+}
+
+#[test]
+fn attribute_only() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+# Heading 1
+
+## Heading 2
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
+            heading: HeadingConfig {
+                render: HeadingRender::AttributeOnly,
+                ..Default::default()
+            },
+            ..Default::default()
+        },
+        panic_on_error,
+    );
+
+    assert_book_item_eq(
+        &item,
+        &BookItem::Chapter(Chapter {
+            name: "Chapter 1".to_string(),
+            content: "\
+# Heading 1 { data-numbering=1. }
+
+## Heading 2 { data-numbering=1.1. }
+
+<style>\
+    h1[data-numbering]:not([data-numbering=\"\"])::before,\
+    h2[data-numbering]:not([data-numbering=\"\"])::before,\
+    h3[data-numbering]:not([data-numbering=\"\"])::before,\
+    h4[data-numbering]:not([data-numbering=\"\"])::before,\
+    h5[data-numbering]:not([data-numbering=\"\"])::before,\
+    h6[data-numbering]:not([data-numbering=\"\"])::before{\
+        content:attr(data-numbering) \" \"\
+    }
+</style>
+"
+            .to_string(),
+            number: Some(SectionNumber::new(vec![1])),
+            path: Some("chapter_1.md".into()),
+            ..Default::default()
+        }),
+    );
+}
+
+#[test]
+fn markdown_extensions() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+---
+title: Chapter 1
+---
+
+# Heading 1
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    let mut extensions = MarkdownExtension::DEFAULT.to_vec();
+    extensions.push(MarkdownExtension::YamlStyleMetadataBlocks);
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
+            heading: HeadingConfig {
+                render: HeadingRender::AttributeOnly,
+                ..Default::default()
+            },
+            markdown_extensions: Some(extensions),
+            ..Default::default()
+        },
+        panic_on_error,
+    );
+
+    let BookItem::Chapter(chapter) = &item else {
+        unreachable!()
+    };
+    assert!(
+        chapter.content.starts_with(
+            "\
+---
+title: Chapter 1
+---
+
+# Heading 1 { data-numbering=1. }
+"
+        ),
+        "{}",
+        chapter.content,
+    );
+}
+
+#[test]
+fn splice() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+# Chapter 1
+
+* item with \\*escapes\\*
+* another
+
+## Section {#sec .cls}
+
+Setext *heading*
+----------------
+
+> ```rust
+> fn main() {}
+> ```
+>
+> ```rust ln-continue
+> let x = 1;
+> ```
+
+## Closed ##
+
+##
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    NumberingPreprocessor::render_book_item(
+        &mut item,
+        &NumberingConfig {
+            rewrite: RewriteMode::Splice,
+            ..Default::default()
+        },
+        panic_on_error,
+    );
+
+    let BookItem::Chapter(chapter) = &item else {
+        unreachable!()
+    };
+    let (content, assets) = chapter.content.split_once("\n<style>").unwrap();
+    assert_string_eq(
+        content,
+        "\
+# <span class=\"heading numbering\">1. </span>Chapter 1 { data-numbering=1. }
+
+* item with \\*escapes\\*
+* another
+
+## <span class=\"heading numbering\">1.1. </span>Section {#sec .cls data-numbering=1.1.}
+
+<span class=\"heading numbering\">1.2. </span>Setext *heading* { data-numbering=1.2. }
+----------------
+
+> ```rust
+> fn main() {}
+> ```
+>
+> ```rust,ln-start-from-2 ln-continue
+> let x = 1;
+> ```
+
+## <span class=\"heading numbering\">1.3. </span>Closed ## { data-numbering=1.3. }
+
+## <span class=\"heading numbering\">1.4. </span> { data-numbering=1.4. }
+",
+    );
+    assert!(assets.contains("<script defer>"), "{assets}");
+
+    let headings: Vec<_> = Parser::new_ext(&chapter.content, Options::all())
+        .filter_map(|event| match event {
+            Event::Start(Tag::Heading { id, attrs, .. }) => Some((id, attrs)),
+            _ => None,
+        })
+        .collect();
+    let numbering = |number: &'static str| vec![("data-numbering".into(), Some(number.into()))];
+    assert_eq!(
+        headings,
+        [
+            (None, numbering("1.")),
+            (Some("sec".into()), numbering("1.1.")),
+            (None, numbering("1.2.")),
+            (None, numbering("1.3.")),
+            (None, numbering("1.4.")),
+        ],
+    );
+}
+
+#[test]
+fn splice_heading_start() {
+    let content = "\
+# \\*foo\\*
+
+## &amp; entity
+
+## `code` first
+
+\\*Setext\\*
+---
+
+   ### Indented
+";
+    for (format, expected) in [
+        (
+            OutputFormat::Html,
+            "\
+# <span class=\"heading numbering\">1. </span>\\*foo\\* { data-numbering=1. }
+
+## <span class=\"heading numbering\">1.1. </span>&amp; entity { data-numbering=1.1. }
+
+## <span class=\"heading numbering\">1.2. </span>`code` first { data-numbering=1.2. }
+
+<span class=\"heading numbering\">1.3. </span>\\*Setext\\* { data-numbering=1.3. }
+---
+
+   ### <span class=\"heading numbering\">1.3.1. </span>Indented { data-numbering=1.3.1. }
+",
+        ),
+        (
+            OutputFormat::Text,
+            "\
+# 1. \\*foo\\*
+
+## 1.1. &amp; entity
+
+## 1.2. `code` first
+
+1\\.3\\. \\*Setext\\*
+---
+
+   ### 1.3.1. Indented
+",
+        ),
+    ] {
+        let mut item = BookItem::Chapter(Chapter {
+            name: "Chapter 1".to_string(),
+            content: content.to_string(),
+            number: Some(SectionNumber::new(vec![1])),
+            path: Some("chapter_1.md".into()),
+            ..Default::default()
+        });
+        let config = NumberingConfig {
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
+            assets: AssetsConfig {
+                mode: AssetsMode::Files,
+                ..Default::default()
+            },
+            rewrite: RewriteMode::Splice,
+            ..Default::default()
+        };
+        NumberingPreprocessor::new()
+            .render_book_item_with(
+                &mut item,
+                &config,
+                &Assets::new(&config),
+                format,
+                Path::new("src"),
+                panic_on_error,
+            )
+            .unwrap();
+
+        let BookItem::Chapter(chapter) = &item else {
+            unreachable!()
+        };
+        assert_string_eq(&chapter.content, expected);
+    }
+}
+
+#[test]
+fn splice_text() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+Chapter 1
+=========
+
++ list
+
+### Unnumbered level\r
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let mut item = BookItem::Chapter(chapter);
+
+    let config = NumberingConfig {
+        rewrite: RewriteMode::Splice,
+        ..Default::default()
+    };
+    let mut warnings = 0;
+    NumberingPreprocessor::new()
+        .render_book_item_with(
+            &mut item,
+            &config,
+            &Assets::new(&config),
+            OutputFormat::Text,
+            Path::new("src"),
+            |_| warnings += 1,
+        )
+        .unwrap();
+
+    let BookItem::Chapter(chapter) = &item else {
+        unreachable!()
+    };
+    assert_eq!(warnings, 1);
+    assert_string_eq(
+        &chapter.content,
+        "\
+1\\. Chapter 1
+=========
+
++ list
+
+### 1.0.1. Unnumbered level\r
+",
+    );
+}
+
+#[test]
+fn verify_roundtrip() {
+    let chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "\
+# Heading
+
+Set in ~subscript~.
+"
+        .to_string(),
+        number: Some(SectionNumber::new(vec![1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+
+    for (rewrite, expected) in [
+        (
+            RewriteMode::Serialize,
+            &[
+                "Chapter \"Chapter 1\" is changed by `mdbook-numbering` besides the numbers: \
+                expected `Start(Subscript)`, found `InlineHtml(Borrowed(\"<sub>\"))`. \
+                Consider using `rewrite = \"splice\"` in the config.\n  \
+                --> src/chapter_1.md:3:8",
+            ][..],
+        ),
+        (RewriteMode::Splice, &[]),
+    ] {
+        let mut item = BookItem::Chapter(chapter.clone());
+        let mut warnings = Vec::new();
+        NumberingPreprocessor::render_book_item(
+            &mut item,
+            &NumberingConfig {
+                rewrite,
+                verify_roundtrip: true,
+                ..Default::default()
+            },
+            |err| warnings.push(err.to_string()),
+        );
+        assert_eq!(warnings, expected);
+    }
+}
+
+#[test]
+fn serialize_error() {
+    let events = [Event::Start(Tag::Paragraph), Event::End(TagEnd::Link)];
+    let err = NumberingPreprocessor::serialize(events.into_iter(), &[], 0).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Unexpected event while reconstructing Markdown"
+    );
+}
+
+#[test]
+fn chapter_error_policies() {
+    let chapter = |name: &str, number: u32, content: &str| {
+        BookItem::Chapter(Chapter {
+            name: name.to_string(),
+            content: content.to_string(),
+            number: Some(SectionNumber::new(vec![number])),
+            path: Some(format!("chapter_{number}.md").into()),
+            ..Default::default()
+        })
+    };
+    let book = Book::new_with_items(vec![
+        chapter("Chapter 1", 1, "# One\n\n#### Deep\n"),
+        chapter("Broken", 2, "# Two\n"),
+    ]);
+    let preprocessor = NumberingPreprocessor::new();
+    let render_chapter = |ch: &Chapter,
+                          config: &NumberingConfig,
+                          assets: &Assets,
+                          format,
+                          src: &Path,
+                          cb: &mut dyn FnMut(Error)| {
+        if ch.name == "Broken" {
+            bail!("Failed to number chapter \"{}\"", ch.name);
+        }
+        preprocessor.render_chapter(ch, config, assets, format, src, cb)
+    };
+
+    for policy in ["fail", "skip"] {
+        let root = env::temp_dir().join(format!("mdbook-numbering-chapter-error-{policy}"));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        let book_toml = format!(
+            "[book]\n\n[preprocessor.numbering]\non-chapter-error = \"{policy}\"\n\
+            outline-json = \"outline.json\"\ndiagnostics = {{ json = \"diagnostics.json\" }}"
+        );
+        let config = Config::from_str(&book_toml).unwrap();
+        let ctx = PreprocessorContext::new(root.clone(), config, "html".into());
+
+        let result = preprocessor.run_with(&ctx, book.clone(), render_chapter);
+
+        // Both files are written, even if the build fails.
+        let outline = fs::read_to_string(root.join("outline.json")).unwrap();
+        assert!(outline.contains("\"Broken\""), "{outline}");
+        let diagnostics = fs::read_to_string(root.join("diagnostics.json")).unwrap();
+        let codes: Vec<_> = diagnostics
+            .lines()
+            .map(|line| {
+                let json: serde_json::Value = serde_json::from_str(line).unwrap();
+                (
+                    json["name"].as_str().unwrap().to_owned(),
+                    json["chapter"].clone(),
+                )
+            })
+            .collect();
+
+        if policy == "fail" {
+            let err = result.unwrap_err();
+            assert_eq!(err.to_string(), "Failed to number chapter \"Broken\"");
+            assert_eq!(codes, [("heading-too-deep".to_owned(), "1.".into())]);
+            continue;
+        }
+
+        let numbered = result.unwrap();
+        let contents: Vec<_> = numbered.chapters().map(|ch| ch.content.as_str()).collect();
+        assert!(
+            contents[0].contains("data-numbering=1.0.0.1."),
+            "{}",
+            contents[0]
+        );
+        assert_eq!(contents[1], "# Two\n");
+        assert_eq!(
+            codes,
+            [
+                ("heading-too-deep".to_owned(), "1.".into()),
+                ("chapter-skipped".to_owned(), "2.".into()),
+            ],
+        );
+    }
 }
@@ -0,0 +1,40 @@
+use crate::code::gutter_css;
+use crate::{CodeConfig, NumberingConfig};
+
+#[test]
+fn gutter_default() {
+    assert_eq!(
+        gutter_css(&CodeConfig::default()),
+        ".hljs-ln-n{color:#ccc;border-right:1px solid #ccc;padding:0 5px 0 0}\
+        .ayu .hljs-ln-n{color:#5c6773;border-right:1px solid #5c6773}\
+        .coal .hljs-ln-n{color:#505254;border-right:1px solid #505254}\
+        .navy .hljs-ln-n{color:#505274;border-right:1px solid #505274}",
+    );
+}
+
+#[test]
+fn gutter_configured() {
+    let config: NumberingConfig = toml::toml! {
+        [code.gutter]
+        color = "#999"
+        padding = "0 1em 0 0"
+
+        [code.theme.navy]
+        color = "#fff"
+        border = "2px dashed #fff"
+
+        [code.theme.rust]
+        padding = "0"
+    }
+    .try_into()
+    .unwrap();
+
+    assert_eq!(
+        gutter_css(&config.code),
+        ".hljs-ln-n{color:#999;border-right:1px solid #ccc;padding:0 1em 0 0}\
+        .ayu .hljs-ln-n{border-right:1px solid #5c6773}\
+        .coal .hljs-ln-n{border-right:1px solid #505254}\
+        .navy .hljs-ln-n{color:#fff;border-right:2px dashed #fff}\
+        .rust .hljs-ln-n{padding:0}",
+    );
+}
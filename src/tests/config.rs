@@ -1,6 +1,16 @@
 use mdbook_preprocessor::config::Config;
 
-use crate::{CodeConfig, HeadingConfig, NumberingConfig, NumberingPreprocessor, NumberingStyle};
+use crate::{
+    CodeConfig, GutterStyle, HeadingConfig, HeadingRender, MarkdownExtension, NumberingConfig,
+    NumberingPreprocessor, NumberingStyle,
+};
+
+/// The Markdown extensions for the `html` renderer without settings in `[output.html]`.
+fn html_default_extensions() -> Option<Vec<MarkdownExtension>> {
+    let mut extensions = MarkdownExtension::DEFAULT.to_vec();
+    extensions.push(MarkdownExtension::SmartPunctuation);
+    Some(extensions)
+}
 
 #[test]
 fn from_str() {
@@ -67,10 +77,14 @@ fn all() {
     assert_eq!(
         config,
         NumberingConfig {
-            code: CodeConfig { enable: true },
+            code: CodeConfig {
+                enable: true,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..NumberingConfig::default()
         }
@@ -81,8 +95,9 @@ fn all() {
 fn full() {
     fn test_config(value: toml::Value, expected: NumberingConfig) {
         let config: Config = value.try_into().unwrap();
-        let config = NumberingPreprocessor::get_config(&config, |err| panic!("{err}"));
+        let mut config = NumberingPreprocessor::get_config(&config, "html").unwrap();
 
+        assert_eq!(config.markdown_extensions.take(), html_default_extensions());
         assert_eq!(config, expected);
     }
 
@@ -92,10 +107,14 @@ fn full() {
             numbering-style = "consecutive"
         },
         NumberingConfig {
-            code: CodeConfig { enable: true },
+            code: CodeConfig {
+                enable: true,
+                ..Default::default()
+            },
             heading: HeadingConfig {
                 enable: true,
                 numbering_style: NumberingStyle::Consecutive,
+                ..Default::default()
             },
             ..NumberingConfig::default()
         },
@@ -125,12 +144,271 @@ fn preprocessors_not_a_list() {
     };
 
     let config: Config = config.try_into().unwrap();
-    let numbering_config = NumberingPreprocessor::get_config(&config, |err| {
-        assert_eq!(
-            err.to_string(),
-            "Failed to deserialize `preprocessor.numbering`"
+    let err = NumberingPreprocessor::get_config(&config, "html").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to deserialize `preprocessor.numbering`: \
+            unknown field `enable`, expected one of `after`, `assets`, `before`, `code`, \
+            `command`, `deny-warnings`, `diagnostics`, `heading`, `ignore-config-errors`, \
+            `markdown-extensions`, `on-chapter-error`, `optional`, `outline-json`, `renderer`, \
+            `renderers`, `rewrite`, `verify-roundtrip`."
+    );
+}
+
+#[test]
+fn renderer_profile() {
+    let config: Config = toml::toml! {
+        [preprocessor.numbering.heading]
+        numbering-style = "top"
+
+        [preprocessor.numbering.renderer.pdf.code]
+        enable = false
+
+        [preprocessor.numbering.renderer.pdf.heading]
+        render = "attribute-only"
+    }
+    .try_into()
+    .unwrap();
+
+    let html = NumberingPreprocessor::get_config(&config, "html").unwrap();
+    assert_eq!(
+        html,
+        NumberingConfig {
+            heading: HeadingConfig {
+                numbering_style: NumberingStyle::Top,
+                ..Default::default()
+            },
+            markdown_extensions: html_default_extensions(),
+            ..Default::default()
+        }
+    );
+
+    let pdf = NumberingPreprocessor::get_config(&config, "pdf").unwrap();
+    assert_eq!(
+        pdf,
+        NumberingConfig {
+            code: CodeConfig {
+                enable: false,
+                ..Default::default()
+            },
+            heading: HeadingConfig {
+                numbering_style: NumberingStyle::Top,
+                render: HeadingRender::AttributeOnly,
+                ..Default::default()
+            },
+            ..Default::default()
+        }
+    );
+}
+
+#[test]
+fn renderer_profile_invalid() {
+    let config: Config = toml::toml! {
+        [preprocessor.numbering.renderer.pdf.code]
+        enable = "false"
+    }
+    .try_into()
+    .unwrap();
+
+    let err = NumberingPreprocessor::get_config(&config, "pdf").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to deserialize `preprocessor.numbering.renderer.pdf`: \
+            invalid type: string \"false\", expected a boolean for key `code.enable`."
+    );
+}
+
+#[test]
+fn renderers() {
+    let config: NumberingConfig = toml::toml! {
+        renderers = ["html", "markdown"]
+    }
+    .try_into()
+    .unwrap();
+    assert!(config.supports_renderer("html"));
+    assert!(config.supports_renderer("markdown"));
+    assert!(!config.supports_renderer("pdf"));
+
+    assert!(NumberingConfig::default().supports_renderer("pdf"));
+}
+
+#[test]
+fn preprocessor_order() {
+    fn warnings(value: toml::Value) -> Vec<String> {
+        let config: Config = value.try_into().unwrap();
+        let mut warnings = Vec::new();
+        NumberingPreprocessor::new().validate_config(&config, |err| warnings.push(err.to_string()));
+        warnings
+    }
+
+    // Preprocessors run alphabetically if not ordered explicitly.
+    assert_eq!(
+        warnings(toml::toml! {
+            [preprocessor.katex]
+            [preprocessor.numbering]
+        }),
+        Vec::<String>::new(),
+    );
+    assert_eq!(
+        warnings(toml::toml! {
+            [preprocessor.numbering]
+            after = ["toc"]
+            [preprocessor.toc]
+        }),
+        Vec::<String>::new(),
+    );
+
+    assert_eq!(
+        warnings(toml::toml! {
+            [preprocessor.numbering]
+            [preprocessor.toc]
+        }),
+        ["Preprocessor `numbering` runs before `toc`, \
+            but it should run after `toc` preprocessors to number their output. \
+            Consider adding `after = [\"toc\"]` to `[preprocessor.numbering]`."],
+    );
+    assert_eq!(
+        warnings(toml::toml! {
+            [preprocessor.math]
+            command = "mdbook-katex"
+            after = ["numbering"]
+            [preprocessor.numbering]
+        }),
+        ["Preprocessor `numbering` runs before `math`, \
+            but it should run after `katex` preprocessors to number their output. \
+            Consider adding `after = [\"math\"]` to `[preprocessor.numbering]`."],
+    );
+    assert_eq!(
+        warnings(toml::toml! {
+            [preprocessor.numbering]
+            before = ["links"]
+        }),
+        ["Preprocessor `numbering` runs before `links`, \
+            but it should run after `links` preprocessors to number their output. \
+            Consider adding `after = [\"links\"]` to `[preprocessor.numbering]`."],
+    );
+}
+
+#[test]
+fn did_you_mean() {
+    fn error(value: toml::Value) -> String {
+        let config: Config = value.try_into().unwrap();
+        let err = NumberingPreprocessor::get_config(&config, "html").unwrap_err();
+        err.to_string()
+    }
+
+    assert_eq!(
+        error(toml::toml! {
+            [preprocessor.numbering.heading]
+            numbering_style = "top"
+        }),
+        "Failed to deserialize `preprocessor.numbering`: \
+        unknown field `numbering_style`, expected one of `enable`, `numbering-style`, `render` \
+        in `heading`. Did you mean `numbering-style`?",
+    );
+    assert_eq!(
+        error(toml::toml! {
+            [preprocessor.numbering.heading]
+            numbering-style = "tpo"
+        }),
+        "Failed to deserialize `preprocessor.numbering`: \
+        unknown variant `tpo`, expected `consecutive` or `top` in `heading.numbering-style`. \
+        Did you mean `top`?",
+    );
+    assert_eq!(
+        error(toml::toml! {
+            [preprocessor.numbering]
+            colour = "red"
+        }),
+        "Failed to deserialize `preprocessor.numbering`: \
+        unknown field `colour`, expected one of `after`, `assets`, `before`, `code`, `command`, \
+        `deny-warnings`, `diagnostics`, `heading`, `ignore-config-errors`, \
+        `markdown-extensions`, `on-chapter-error`, `optional`, `outline-json`, `renderer`, \
+        `renderers`, `rewrite`, `verify-roundtrip`.",
+    );
+}
+
+#[test]
+fn markdown_extensions_from_html() {
+    let config: Config = toml::toml! {
+        [preprocessor.numbering]
+
+        [output.html]
+        smart-punctuation = true
+        definition-lists = false
+    }
+    .try_into()
+    .unwrap();
+    let config = NumberingPreprocessor::get_config(&config, "html").unwrap();
+    let mut expected = MarkdownExtension::DEFAULT.to_vec();
+    expected.retain(|extension| *extension != MarkdownExtension::DefinitionList);
+    expected.push(MarkdownExtension::SmartPunctuation);
+    assert_eq!(config.markdown_extensions, Some(expected));
+
+    let config: Config = toml::toml! {
+        [preprocessor.numbering]
+    }
+    .try_into()
+    .unwrap();
+    let html = NumberingPreprocessor::get_config(&config, "html").unwrap();
+    assert_eq!(html.markdown_extensions, html_default_extensions());
+    let markdown = NumberingPreprocessor::get_config(&config, "markdown").unwrap();
+    assert_eq!(markdown.markdown_extensions, None);
+
+    let config: Config = toml::toml! {
+        [preprocessor.numbering]
+        markdown-extensions = ["tables", "wikilinks"]
+
+        [output.html]
+        smart-punctuation = true
+    }
+    .try_into()
+    .unwrap();
+    let config = NumberingPreprocessor::get_config(&config, "html").unwrap();
+    assert_eq!(
+        config.markdown_extensions,
+        Some(vec![
+            MarkdownExtension::Tables,
+            MarkdownExtension::Wikilinks
+        ]),
+    );
+}
+
+#[test]
+fn config_builder() {
+    let built = NumberingConfig::new()
+        .heading(
+            HeadingConfig::new()
+                .numbering_style(NumberingStyle::Top)
+                .render(HeadingRender::AttributeOnly),
+        )
+        .code(
+            CodeConfig::new()
+                .all_blocks(true)
+                .theme("navy", GutterStyle::new().color("#6e7180")),
         )
-    });
+        .renderers(vec!["html".to_string()]);
+
+    let parsed = NumberingConfig::from_toml_str(
+        r##"
+renderers = ["html"]
 
-    assert_eq!(numbering_config, NumberingConfig::default());
+[heading]
+numbering-style = "top"
+render = "attribute-only"
+
+[code]
+all-blocks = true
+theme.navy.color = "#6e7180"
+"##,
+    )
+    .unwrap();
+    assert_eq!(built, parsed);
+
+    let err = NumberingConfig::from_toml_str("heading.numbering-style = \"tpo\"").unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "Failed to parse the configuration: unknown variant `tpo`, \
+        expected `consecutive` or `top` for key `heading.numbering-style`. Did you mean `top`?"
+    );
 }
@@ -0,0 +1,67 @@
+use std::path::Path;
+
+use mdbook_preprocessor::book::SectionNumber;
+use serde_json::{Value, json};
+
+use crate::diagnostic::{Diagnostic, DiagnosticKind, Severity, Source};
+
+#[test]
+fn line_column() {
+    let source = Source::new(None, "# Title\n\n> ## Größe\n", None);
+    assert_eq!(source.line_column(0), (1, 1));
+    assert_eq!(source.line_column(2), (1, 3));
+    assert_eq!(source.line_column(8), (2, 1));
+    assert_eq!(source.line_column(11), (3, 3));
+    assert_eq!(source.line_column("# Title\n\n> ## Grö".len()), (3, 9));
+}
+
+#[test]
+fn locate() {
+    let number = SectionNumber::new(vec![1, 2]);
+    let source = Source::new(
+        Some(Path::new("src/intro.md")),
+        "# Title\n\n### Deep\n",
+        Some(&number),
+    );
+    let diagnostic = source.locate(
+        9,
+        Diagnostic::new(DiagnosticKind::HeadingTooDeep, "Heading too deep."),
+    );
+    assert_eq!(
+        diagnostic.to_string(),
+        "Heading too deep.\n  --> src/intro.md:3:1",
+    );
+    assert_eq!(
+        serde_json::from_str::<Value>(&diagnostic.to_json(Severity::Warning)).unwrap(),
+        json!({
+            "severity": "warning",
+            "code": "N001",
+            "name": "heading-too-deep",
+            "message": "Heading too deep.",
+            "file": "src/intro.md",
+            "line": 3,
+            "column": 1,
+            "chapter": "1.2.",
+        }),
+    );
+
+    let source = Source::new(None, "# Title\n\n### Deep\n", None);
+    let diagnostic = source.locate(
+        9,
+        Diagnostic::new(DiagnosticKind::HeadingTooDeep, "Heading too deep."),
+    );
+    assert_eq!(diagnostic.to_string(), "Heading too deep.");
+    assert_eq!(
+        serde_json::from_str::<Value>(&diagnostic.to_json(Severity::Error)).unwrap(),
+        json!({
+            "severity": "error",
+            "code": "N001",
+            "name": "heading-too-deep",
+            "message": "Heading too deep.",
+            "file": null,
+            "line": null,
+            "column": null,
+            "chapter": null,
+        }),
+    );
+}
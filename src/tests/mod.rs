@@ -1,3 +1,5 @@
 mod chapters;
+mod code;
 mod config;
+mod diagnostic;
 mod name;
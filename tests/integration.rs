@@ -1,16 +1,22 @@
 //! Tests for mdbook-numbering preprocessor integration.
 
+use std::fs;
 use std::io::Write;
+use std::path::{Path, PathBuf};
 use std::process::{Command, Stdio};
 use std::str::FromStr;
 
-use mdbook_numbering::NumberingPreprocessor;
-use mdbook_preprocessor::book::{Book, BookItem, Chapter};
+use mdbook_numbering::{
+    AssetsMode, DiagnosticKind, FormattedNumber, HeadingConfig, HeadingContext, HeadingRender,
+    NumberFormatter, NumberHeadings, NumberingConfig, NumberingPreprocessor,
+};
+use mdbook_preprocessor::book::{Book, BookItem, Chapter, SectionNumber};
 use mdbook_preprocessor::config::Config;
 use mdbook_preprocessor::{Preprocessor, PreprocessorContext};
 use prettydiff::basic::DiffOp;
 use prettydiff::diff_lines;
 use prettydiff::owo_colors::OwoColorize as _;
+use pulldown_cmark::{Event, Parser};
 
 #[track_caller]
 fn assert_string_eq(actual: &str, expected: &str) {
@@ -70,6 +76,21 @@ fn run(ctx: &PreprocessorContext, book: Book, expected_stderr: &str) -> Book {
     serde_json::from_slice(&output.stdout).unwrap()
 }
 
+/// An empty directory named `name` in the temporary directory of the tests,
+/// used as the root of a book.
+fn temp_root(name: &str) -> PathBuf {
+    let root = Path::new(env!("CARGO_TARGET_TMPDIR")).join(name);
+    let _ = fs::remove_dir_all(&root);
+    fs::create_dir_all(&root).unwrap();
+    root
+}
+
+/// The context of the `html` renderer for the book in `root`, configured with `book_toml`.
+fn html_context(root: &Path, book_toml: &str) -> PreprocessorContext {
+    let config = Config::from_str(book_toml).unwrap();
+    PreprocessorContext::new(root.to_owned(), config, "html".into())
+}
+
 #[track_caller]
 fn assert_book_equal(actual: &Book, expected: &Book) {
     assert_eq!(
@@ -267,7 +288,7 @@ enable = false
         items: vec![
             BookItem::Chapter(Chapter {
                 name: "Test1".to_string(),
-                content: include_str!("./md/test4.output.md").to_string(),
+                content: include_str!("./md/test4.input.md").to_string(),
                 number: Some(vec![1].into_iter().collect()),
                 path: Some("./md/test4.input.md".into()),
                 ..Default::default()
@@ -296,39 +317,38 @@ enable = "false"
     );
 
     let book = Book {
-        items: vec![
-            BookItem::Chapter(Chapter {
-                name: "Test1".to_string(),
-                content: include_str!("./md/test1.input.md").to_string(),
-                number: Some(vec![1].into_iter().collect()),
-                path: Some("./md/test1.input.md".into()),
-                ..Default::default()
-            }),
-            BookItem::Separator,
-            BookItem::PartTitle("Title 1".to_string()),
-        ],
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: include_str!("./md/test1.input.md").to_string(),
+            number: Some(vec![1].into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
     };
-    let preprocessed = run(
-        &ctx,
-        book,
-        "Using default config for mdbook-numbering due to config error: \
-Failed to deserialize `preprocessor.numbering`
+
+    let mut process = Command::new("cargo")
+        .args(["run", "--quiet", "--bin", "mdbook-numbering"])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .expect("Failed to start mdbook-numbering preprocessor");
+    write!(
+        process.stdin.as_mut().unwrap(),
+        "{}",
+        serde_json::to_string(&(&ctx, book)).unwrap()
+    )
+    .unwrap();
+    let output = process.wait_with_output().unwrap();
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"");
+    assert_string_eq(
+        &String::from_utf8_lossy(&output.stderr),
+        "\
+Failed to deserialize `preprocessor.numbering`: \
+invalid type: string \"false\", expected a boolean in `code.enable`.
 ",
     );
-    let expected = Book {
-        items: vec![
-            BookItem::Chapter(Chapter {
-                name: "Test1".to_string(),
-                content: include_str!("./md/test1.output.md").to_string(),
-                number: Some(vec![1].into_iter().collect()),
-                path: Some("./md/test1.input.md".into()),
-                ..Default::default()
-            }),
-            BookItem::Separator,
-            BookItem::PartTitle("Title 1".to_string()),
-        ],
-    };
-    assert_book_equal(&preprocessed, &expected);
 }
 
 #[test]
@@ -341,6 +361,7 @@ fn illformed_enable() {
 
 [preprocessor.numbering]
 enable = "true"
+ignore-config-errors = true
 "#,
         )
         .unwrap(),
@@ -365,7 +386,11 @@ enable = "true"
         book,
         "\
 Using default config for mdbook-numbering due to config error: \
-Failed to deserialize `preprocessor.numbering`
+Failed to deserialize `preprocessor.numbering`: \
+unknown field `enable`, expected one of `after`, `assets`, `before`, `code`, `command`, \
+`deny-warnings`, `diagnostics`, `heading`, `ignore-config-errors`, \
+`markdown-extensions`, `on-chapter-error`, `optional`, `outline-json`, `renderer`, \
+`renderers`, `rewrite`, `verify-roundtrip`.
 ",
     );
     let expected = Book {
@@ -439,6 +464,7 @@ fn supports() {
             "mdbook-numbering",
             "--",
             "supports",
+            "html",
         ])
         .output()
         .expect("Failed to execute process");
@@ -447,6 +473,72 @@ fn supports() {
     assert_eq!(output.stderr, b"");
 }
 
+#[test]
+fn supports_renderers() {
+    let root = temp_root("supports_renderers");
+    fs::write(
+        root.join("book.toml"),
+        "[preprocessor.numbering]\nrenderers = [\"html\"]\n",
+    )
+    .unwrap();
+
+    let supports = |renderer: &str| {
+        Command::new(env!("CARGO_BIN_EXE_mdbook-numbering"))
+            .args(["supports", renderer])
+            .current_dir(&root)
+            .output()
+            .expect("Failed to execute process")
+    };
+
+    let output = supports("html");
+    assert!(output.status.success());
+    assert_eq!(output.stderr, b"");
+
+    let output = supports("pdf");
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"");
+    assert_eq!(
+        output.stderr,
+        b"mdbook-numbering: renderer `pdf` is not supported\n"
+    );
+}
+
+#[test]
+fn supports_invalid_book_toml() {
+    let root = temp_root("supports_invalid_book_toml");
+    fs::write(root.join("book.toml"), "[preprocessor.numbering\n").unwrap();
+
+    let output = Command::new(env!("CARGO_BIN_EXE_mdbook-numbering"))
+        .args(["supports", "html"])
+        .current_dir(&root)
+        .output()
+        .expect("Failed to execute process");
+    assert!(output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.starts_with("mdbook-numbering: Failed to read `book.toml`: "),
+        "{stderr}"
+    );
+}
+
+#[test]
+fn supports_missing_renderer() {
+    let output = Command::new("cargo")
+        .args([
+            "run",
+            "--quiet",
+            "--bin",
+            "mdbook-numbering",
+            "--",
+            "supports",
+        ])
+        .output()
+        .expect("Failed to execute process");
+    assert!(!output.status.success());
+    assert_eq!(output.stdout, b"");
+    assert_eq!(output.stderr, b"missing renderer name for `supports`\n");
+}
+
 #[test]
 fn wrong_argument() {
     let output = Command::new("cargo")
@@ -475,3 +567,631 @@ fn empty() {
     assert_eq!(output.stdout, b"");
     assert_eq!(output.stderr, b"Unable to parse the input\n",);
 }
+
+#[test]
+fn assets_files() {
+    let root = temp_root("assets_files");
+
+    let book = Book {
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: include_str!("./md/test1.input.md").to_string(),
+            number: Some(vec![1, 2].into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
+    };
+    let expected = Book {
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: "\
+# <span class=\"heading numbering\">1.2. </span>Heading { data-numbering=1.2. }
+
+````c
+int main() {
+    printf(\"Hello, world!\");
+}
+````"
+                .to_string(),
+            number: Some(vec![1, 2].into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
+    };
+
+    let ctx = html_context(
+        &root,
+        r#"
+[book]
+
+[preprocessor.numbering]
+assets = { mode = "files" }
+heading = { numbering-style = "top" }
+"#,
+    );
+    let preprocessed = run(
+        &ctx,
+        book.clone(),
+        "\
+mdbook-numbering: `mdbook-numbering/numbering.css` is not listed in `output.html.additional-css`, \
+so it will not be loaded by the rendered book. \
+Consider adding `additional-css = [\"mdbook-numbering/numbering.css\"]` to `[output.html]`.
+mdbook-numbering: `mdbook-numbering/numbering.js` is not listed in `output.html.additional-js`, \
+so it will not be loaded by the rendered book. \
+Consider adding `additional-js = [\"mdbook-numbering/numbering.js\"]` to `[output.html]`.
+",
+    );
+    assert_book_equal(&preprocessed, &expected);
+
+    let css = fs::read_to_string(root.join("mdbook-numbering/numbering.css")).unwrap();
+    assert!(css.starts_with("span.heading.numbering{"));
+    assert!(!css.contains("@media print"));
+    assert!(css.contains(".hljs-ln-n{color:#ccc;"));
+    let js = fs::read_to_string(root.join("mdbook-numbering/numbering.js")).unwrap();
+    assert!(js.starts_with("window.addEventListener('DOMContentLoaded', function() { "));
+
+    let ctx = html_context(
+        &root,
+        r#"
+[book]
+
+[preprocessor.numbering]
+assets = { mode = "files", dir = "./assets" }
+
+[output.html]
+additional-css = ["./assets/numbering.css"]
+additional-js = ["assets/numbering.js"]
+"#,
+    );
+    run(
+        &ctx,
+        book,
+        "\
+mdbook-numbering: Heading level h1 found, \
+but numbering \"1.2.\" for chapter \"Test1\" has more levels. \
+Consider using `numbering-style = \"top\"` in the config, \
+if you want the top heading to be level 1.
+  --> src/md/test1.input.md:1:1
+",
+    );
+
+    let css = fs::read_to_string(root.join("assets/numbering.css")).unwrap();
+    assert!(css.contains("@media print"));
+}
+
+#[test]
+fn assets_overrides() {
+    let root = temp_root("assets_overrides");
+    fs::create_dir_all(root.join("theme")).unwrap();
+    fs::write(
+        root.join("theme/heading.css"),
+        "span.heading.numbering{color:red}",
+    )
+    .unwrap();
+    fs::write(root.join("theme/brand.css"), ".hljs-ln-n{color:blue}").unwrap();
+    fs::write(root.join("theme/brand.js"), "console.log('brand')").unwrap();
+    fs::write(
+        root.join("theme/print.css"),
+        "@media print{.hljs-ln-n{display:none}}\n",
+    )
+    .unwrap();
+    fs::write(root.join("theme/print.js"), "console.log('print');\n").unwrap();
+
+    let preprocessor = NumberingPreprocessor::new();
+    let book = Book {
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: include_str!("./md/test1.input.md").to_string(),
+            number: Some(vec![1].into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
+    };
+
+    let ctx = html_context(
+        &root,
+        r#"
+[book]
+
+[preprocessor.numbering.assets]
+heading-css = "theme/heading.css"
+extra-css = ["theme/brand.css", "theme/print.css"]
+extra-js = ["theme/brand.js", "theme/print.js"]
+"#,
+    );
+    let preprocessed = preprocessor.run(&ctx, book.clone()).unwrap();
+    let BookItem::Chapter(chapter) = &preprocessed.items[0] else {
+        unreachable!()
+    };
+    assert!(
+        chapter
+            .content
+            .contains("<style>span.heading.numbering{color:red}</style>\n")
+    );
+    assert!(chapter.content.ends_with(
+        "</style>\n\
+        <style>\n.hljs-ln-n{color:blue}\n@media print{.hljs-ln-n{display:none}}\n</style>\n\
+        <script>\nconsole.log('brand');\nconsole.log('print');\n</script>\n"
+    ));
+
+    let ctx = html_context(
+        &root,
+        r#"
+[book]
+
+[preprocessor.numbering.assets]
+code-js = "theme/missing.js"
+"#,
+    );
+    let err = preprocessor.run(&ctx, book).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        format!(
+            "Failed to read asset file `{}`",
+            root.join("theme/missing.js").display()
+        ),
+    );
+}
+
+#[test]
+fn markdown_renderer() {
+    let ctx = PreprocessorContext::new(
+        file!().into(),
+        Config::from_str("[book]\n\n[preprocessor.numbering]").unwrap(),
+        "markdown".into(),
+    );
+
+    let book = Book {
+        items: vec![
+            BookItem::Chapter(Chapter {
+                name: "Test1".to_string(),
+                content: include_str!("./md/test6.input.md").to_string(),
+                number: Some(vec![1].into_iter().collect()),
+                path: Some("./md/test6.input.md".into()),
+                ..Default::default()
+            }),
+            BookItem::Chapter(Chapter {
+                name: "Test2".to_string(),
+                content: include_str!("./md/test6.input.md").to_string(),
+                number: None,
+                path: Some("./md/test6.input.md".into()),
+                ..Default::default()
+            }),
+        ],
+    };
+    let preprocessed = run(&ctx, book, "");
+    let expected = Book {
+        items: vec![
+            BookItem::Chapter(Chapter {
+                name: "Test1".to_string(),
+                content: include_str!("./md/test6.output.md").to_string(),
+                number: Some(vec![1].into_iter().collect()),
+                path: Some("./md/test6.input.md".into()),
+                ..Default::default()
+            }),
+            BookItem::Chapter(Chapter {
+                name: "Test2".to_string(),
+                content: include_str!("./md/test6.input.md").to_string(),
+                number: None,
+                path: Some("./md/test6.input.md".into()),
+                ..Default::default()
+            }),
+        ],
+    };
+    assert_book_equal(&preprocessed, &expected);
+}
+
+#[test]
+fn deny_warnings() {
+    let ctx = PreprocessorContext::new(
+        file!().into(),
+        Config::from_str("[book]\n\n[preprocessor.numbering]\ndeny-warnings = true").unwrap(),
+        "html".into(),
+    );
+    let preprocessor = NumberingPreprocessor::new();
+
+    let chapter = |number: Vec<u32>| Book {
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: include_str!("./md/test1.input.md").to_string(),
+            number: Some(number.into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
+    };
+
+    assert!(preprocessor.run(&ctx, chapter(vec![1])).is_ok());
+
+    let err = preprocessor.run(&ctx, chapter(vec![1, 2])).unwrap_err();
+    assert_eq!(
+        err.to_string(),
+        "mdbook-numbering found 1 warning(s), \
+        which are denied by `deny-warnings = true` in `[preprocessor.numbering]`."
+    );
+}
+
+#[test]
+fn json_diagnostics() {
+    let ctx = PreprocessorContext::new(
+        file!().into(),
+        Config::from_str("[book]\n\n[preprocessor.numbering.diagnostics]\njson = \"-\"").unwrap(),
+        "html".into(),
+    );
+
+    let book = Book {
+        items: vec![BookItem::Chapter(Chapter {
+            name: "Test1".to_string(),
+            content: include_str!("./md/test1.input.md").to_string(),
+            number: Some(vec![1, 2].into_iter().collect()),
+            path: Some("./md/test1.input.md".into()),
+            ..Default::default()
+        })],
+    };
+    run(
+        &ctx,
+        book,
+        "\
+mdbook-numbering: Heading level h1 found, \
+but numbering \"1.2.\" for chapter \"Test1\" has more levels. \
+Consider using `numbering-style = \"top\"` in the config, \
+if you want the top heading to be level 1.
+  --> src/md/test1.input.md:1:1
+{\"severity\":\"warning\",\"code\":\"N002\",\"name\":\"heading-too-shallow\",\
+\"message\":\"Heading level h1 found, \
+but numbering \\\"1.2.\\\" for chapter \\\"Test1\\\" has more levels. \
+Consider using `numbering-style = \\\"top\\\"` in the config, \
+if you want the top heading to be level 1.\",\
+\"file\":\"src/md/test1.input.md\",\"line\":1,\"column\":1,\"chapter\":\"1.2.\"}
+",
+    );
+}
+
+#[test]
+fn library_api() {
+    let mut config = NumberingConfig::default();
+    config.code.enable = false;
+    config.assets.mode = AssetsMode::Files;
+
+    let (markdown, diagnostics) =
+        mdbook_numbering::number_markdown("## Title\n", &SectionNumber::new([1, 2]), &config)
+            .unwrap();
+    assert_eq!(
+        markdown,
+        "## <span class=\"heading numbering\">1.2. </span>Title { data-numbering=1.2. }"
+    );
+    assert!(diagnostics.is_empty());
+
+    let mut chapter = Chapter {
+        name: "Chapter 1".to_string(),
+        content: "# Title\n\n#### Too deep\n".to_string(),
+        number: Some(SectionNumber::new([1])),
+        path: Some("chapter_1.md".into()),
+        ..Default::default()
+    };
+    let diagnostics = mdbook_numbering::number_chapter(&mut chapter, &config).unwrap();
+    assert!(chapter.content.contains("data-numbering=1.0.0.1."));
+    let [diagnostic] = &diagnostics[..] else {
+        panic!("{diagnostics:?}");
+    };
+    assert_eq!(diagnostic.kind, DiagnosticKind::HeadingTooDeep);
+    assert_eq!(diagnostic.kind.code(), "N001");
+    assert_eq!(diagnostic.file.as_deref(), Some(Path::new("chapter_1.md")));
+    assert_eq!(diagnostic.position, Some((3, 1)));
+    assert_eq!(diagnostic.chapter.as_deref(), Some("1."));
+}
+
+#[test]
+fn number_formatter() {
+    struct Lettered;
+
+    impl NumberFormatter for Lettered {
+        fn format(&self, heading: &HeadingContext<'_>) -> FormattedNumber {
+            let chapter = heading.chapter_number.len();
+            let letters: Vec<String> = heading.number[chapter..]
+                .iter()
+                .map(|n| char::from(b'A' + (*n as u8) - 1).to_string())
+                .collect();
+            match heading.level {
+                1 => FormattedNumber::new(format!("Part {}", heading.chapter_number), "part"),
+                _ => FormattedNumber::new(letters.join("."), letters.join("-")),
+            }
+        }
+    }
+
+    let preprocessor = NumberingPreprocessor::with_formatter(Lettered);
+    for (renderer, expected) in [
+        (
+            "html",
+            "# <span class=\"heading numbering\">Part 1. </span>Title { data-numbering=part }\n\n\
+            ## <span class=\"heading numbering\">A </span>First { data-numbering=A }\n\n\
+            ### <span class=\"heading numbering\">A.A </span>Second { data-numbering=A-A }\n\n\
+            ### <span class=\"heading numbering\">A.B </span>Second { data-numbering=A-B }\n",
+        ),
+        (
+            "markdown",
+            "# Part 1. Title\n\n## A First\n\n### A.A Second\n\n### A.B Second\n",
+        ),
+    ] {
+        for rewrite in ["serialize", "splice"] {
+            let ctx = PreprocessorContext::new(
+                file!().into(),
+                Config::from_str(&format!(
+                    "[book]\n\n[preprocessor.numbering]\n\
+                    rewrite = \"{rewrite}\"\nverify-roundtrip = true\ndeny-warnings = true",
+                ))
+                .unwrap(),
+                renderer.into(),
+            );
+            let book = Book {
+                items: vec![BookItem::Chapter(Chapter {
+                    name: "Test".to_string(),
+                    content: "# Title\n\n## First\n\n### Second\n\n### Second\n".to_string(),
+                    number: Some(SectionNumber::new([1])),
+                    path: Some("test.md".into()),
+                    ..Default::default()
+                })],
+            };
+            let book = preprocessor.run(&ctx, book).unwrap();
+            let BookItem::Chapter(chapter) = &book.items[0] else {
+                unreachable!()
+            };
+            let expected = expected.trim_end();
+            assert!(chapter.content.starts_with(expected), "{}", chapter.content);
+        }
+    }
+}
+
+#[test]
+fn number_formatter_escaping() {
+    struct Starred;
+
+    impl NumberFormatter for Starred {
+        fn format(&self, heading: &HeadingContext<'_>) -> FormattedNumber {
+            FormattedNumber::new(format!("*{}* <b>", heading.number), "a }b")
+        }
+    }
+
+    let expected = "# <span class=\"heading numbering\">\\*1.\\* &lt;b&gt; </span>Title \
+        { data-numbering=1. }";
+    let preprocessor = NumberingPreprocessor::with_formatter(Starred);
+    for rewrite in ["serialize", "splice"] {
+        let ctx = PreprocessorContext::new(
+            file!().into(),
+            Config::from_str(&format!(
+                "[book]\n\n[preprocessor.numbering]\nrewrite = \"{rewrite}\"",
+            ))
+            .unwrap(),
+            "html".into(),
+        );
+        let book = Book {
+            items: vec![BookItem::Chapter(Chapter {
+                name: "Test".to_string(),
+                content: "# Title\n".to_string(),
+                number: Some(SectionNumber::new([1])),
+                path: Some("test.md".into()),
+                ..Default::default()
+            })],
+        };
+        let book = preprocessor.run(&ctx, book).unwrap();
+        let BookItem::Chapter(chapter) = &book.items[0] else {
+            unreachable!()
+        };
+        assert!(chapter.content.starts_with(expected), "{}", chapter.content);
+    }
+
+    let number = SectionNumber::new([1]);
+    let config = HeadingConfig::new();
+    let mut events = NumberHeadings::new(Parser::new("# Title\n"), Some(&number), &config)
+        .chapter_name("Test")
+        .formatter(&Starred);
+    let mut markdown = String::new();
+    pulldown_cmark_to_cmark::cmark(events.by_ref(), &mut markdown).unwrap();
+    assert_string_eq(&markdown, expected);
+    let diagnostics = events.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::InvalidNumberAttribute);
+    assert_eq!(
+        diagnostics[0].message,
+        "Heading number attribute \"a }b\" in chapter \"Test\" contains whitespace or braces, \
+        using \"1.\" instead.",
+    );
+}
+
+#[test]
+fn number_headings() {
+    struct Named;
+
+    impl NumberFormatter for Named {
+        fn format(&self, heading: &HeadingContext<'_>) -> FormattedNumber {
+            let label = format!("{} {}", heading.chapter_name, heading.number);
+            FormattedNumber::new(label, heading.number.to_string())
+        }
+    }
+
+    let number = SectionNumber::new([3]);
+    let source = "# Title\n\n#### Deep\n\nText with `code`.\n";
+    let config = HeadingConfig::new().render(HeadingRender::AttributeOnly);
+    let mut events =
+        NumberHeadings::new(Parser::new(source), Some(&number), &config).chapter_name("Test");
+    let mut markdown = String::new();
+    pulldown_cmark_to_cmark::cmark(events.by_ref(), &mut markdown).unwrap();
+    assert_string_eq(
+        &markdown,
+        "# Title { data-numbering=3. }\n\n#### Deep { data-numbering=3.0.0.1. }\n\n\
+        Text with `code`.",
+    );
+    let diagnostics = events.diagnostics();
+    assert_eq!(diagnostics.len(), 1);
+    assert_eq!(diagnostics[0].kind, DiagnosticKind::HeadingTooDeep);
+    assert_eq!(diagnostics[0].chapter.as_deref(), Some("3."));
+    assert!(diagnostics[0].message.contains("\"Test\""));
+
+    let events = NumberHeadings::new(Parser::new(source), Some(&number), &HeadingConfig::new())
+        .chapter_name("Test")
+        .formatter(&Named)
+        .plain_text(true);
+    let events: Vec<Event> = events.collect();
+    assert_eq!(events[1], Event::Text("Test 3. ".into()));
+    assert_eq!(events[2], Event::Text("Title".into()));
+
+    let events = NumberHeadings::new(Parser::new(source), None, &HeadingConfig::new());
+    let mut markdown = String::new();
+    pulldown_cmark_to_cmark::cmark(events, &mut markdown).unwrap();
+    assert_string_eq(
+        &markdown,
+        "# Title { data-numbering }\n\n#### Deep { data-numbering }\n\nText with `code`.",
+    );
+
+    let events = NumberHeadings::new(
+        Parser::new(source),
+        Some(&number),
+        &HeadingConfig::new().enable(false),
+    );
+    assert!(events.eq(Parser::new(source)));
+}
+
+#[test]
+fn outline() {
+    let book = Book {
+        items: vec![
+            BookItem::Chapter(Chapter {
+                name: "Intro".to_string(),
+                content: "# Intro\n\n## Setup {#install}\n\n## Usage of `mdbook`\n\n\
+                    ## Usage of `mdbook`\n"
+                    .to_string(),
+                number: Some(SectionNumber::new([1])),
+                path: Some("intro.md".into()),
+                source_path: Some("intro.md".into()),
+                ..Default::default()
+            }),
+            BookItem::Chapter(Chapter::new_draft("Later", Vec::new())),
+        ],
+    };
+
+    let outline = mdbook_numbering::outline(&book, &NumberingConfig::new());
+    let [intro, later] = &outline.chapters[..] else {
+        panic!("{outline:?}");
+    };
+    assert_eq!(intro.name, "Intro");
+    assert_eq!(intro.number, Some(SectionNumber::new([1])));
+    assert_eq!(intro.source_path.as_deref(), Some(Path::new("intro.md")));
+    let headings: Vec<_> = (intro.headings.iter())
+        .map(|heading| {
+            let number = heading.number.as_ref().map(ToString::to_string);
+            let label = heading.label.as_deref();
+            (
+                number,
+                label,
+                heading.level,
+                heading.text.as_str(),
+                heading.id.as_str(),
+            )
+        })
+        .collect();
+    assert_eq!(
+        headings,
+        [
+            (Some("1.".to_owned()), Some("1."), 1, "Intro", "1-intro"),
+            (Some("1.1.".to_owned()), Some("1.1."), 2, "Setup", "install"),
+            (
+                Some("1.2.".to_owned()),
+                Some("1.2."),
+                2,
+                "Usage of mdbook",
+                "12-usage-of-mdbook"
+            ),
+            (
+                Some("1.3.".to_owned()),
+                Some("1.3."),
+                2,
+                "Usage of mdbook",
+                "13-usage-of-mdbook"
+            ),
+        ],
+    );
+    assert_eq!(later.name, "Later");
+    assert_eq!(later.path, None);
+    assert!(later.headings.is_empty());
+
+    // Without the `<span>` element, the number is not part of the id.
+    let config =
+        NumberingConfig::new().heading(HeadingConfig::new().render(HeadingRender::AttributeOnly));
+    let outline = mdbook_numbering::outline(&book, &config);
+    let ids: Vec<_> = (outline.chapters[0].headings.iter())
+        .map(|heading| heading.id.as_str())
+        .collect();
+    assert_eq!(
+        ids,
+        ["intro", "install", "usage-of-mdbook", "usage-of-mdbook-1"]
+    );
+
+    let root = temp_root("outline");
+    let ctx = html_context(
+        &root,
+        "[book]\n\n[preprocessor.numbering]\noutline-json = \"numbering.json\"",
+    );
+    run(&ctx, book, "");
+    let json = fs::read_to_string(root.join("numbering.json")).unwrap();
+    let json: serde_json::Value = serde_json::from_str(&json).unwrap();
+    assert_eq!(json["chapters"][0]["source-path"], "intro.md");
+    assert_eq!(
+        json["chapters"][0]["headings"][2]["number"],
+        serde_json::json!([1, 2])
+    );
+    assert_eq!(
+        json["chapters"][0]["headings"][2]["id"],
+        "12-usage-of-mdbook"
+    );
+    assert_eq!(json["chapters"][1]["number"], serde_json::Value::Null);
+}
+
+#[test]
+fn diagnostics_in_book_order() {
+    let root = temp_root("diagnostics_in_book_order");
+    let ctx = html_context(
+        &root,
+        "[book]\n\n[preprocessor.numbering]\ndiagnostics = { json = \"diagnostics.json\" }",
+    );
+    let chapter = |number: Vec<u32>, sub_items| {
+        BookItem::Chapter(Chapter {
+            name: format!("Chapter {}", SectionNumber::new(number.clone())),
+            content: "#### Too deep\n".to_string(),
+            path: Some(format!("{}.md", SectionNumber::new(number.clone())).into()),
+            number: Some(SectionNumber::new(number)),
+            sub_items,
+            ..Default::default()
+        })
+    };
+    let book = Book {
+        items: (1..=50)
+            .map(|i| chapter(vec![i], vec![chapter(vec![i, 1], Vec::new())]))
+            .collect(),
+    };
+
+    let book = NumberingPreprocessor::new().run(&ctx, book).unwrap();
+    // Each chapter keeps its own content, whose level 4 heading is numbered after it.
+    for ch in book.chapters() {
+        let number = ch.number.as_ref().unwrap();
+        let padding = "0.".repeat(3 - number.len());
+        let attribute = format!("data-numbering={number}{padding}1.");
+        assert!(
+            ch.content.contains(&attribute),
+            "{}: {}",
+            ch.name,
+            ch.content
+        );
+    }
+    assert_eq!(book.chapters().count(), 100);
+    let json = fs::read_to_string(root.join("diagnostics.json")).unwrap();
+    let chapters: Vec<String> = json
+        .lines()
+        .map(|line| {
+            let diagnostic: serde_json::Value = serde_json::from_str(line).unwrap();
+            diagnostic["chapter"].as_str().unwrap().to_owned()
+        })
+        .collect();
+    let expected: Vec<String> = (1..=50)
+        .flat_map(|i| [format!("{i}."), format!("{i}.1.")])
+        .collect();
+    assert_eq!(chapters, expected);
+}